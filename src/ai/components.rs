@@ -0,0 +1,9 @@
+use ai::state_machine::StateMachine;
+use specs::*;
+
+/// Decides what an AI actor presses, its movement goes through the player states.
+#[derive(Component)]
+#[component(DenseVecStorage)]
+pub struct Brain {
+    pub machine: StateMachine,
+}
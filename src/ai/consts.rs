@@ -0,0 +1,10 @@
+// ranges are (horizontal, vertical) distances from the enemy
+pub const SIGHT_RANGE: (f64, f64) = (900.0, 200.0);
+pub const LOSE_RANGE: (f64, f64) = (1400.0, 400.0);
+pub const ATTACK_RANGE: (f64, f64) = (150.0, 100.0);
+pub const ATTACK_COOLDOWN: f64 = 1.0;
+pub const HEALTH: f64 = 50.0;
+pub const ATTACK_DAMAGE: f64 = 10.0;
+pub const ATTACK_KNOCKBACK_X: f64 = 900.0;
+pub const ATTACK_KNOCKBACK_Y: f64 = 700.0;
+pub const INVINCIBILITY_TIME: f64 = 0.6;
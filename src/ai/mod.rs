@@ -0,0 +1,41 @@
+pub mod components;
+pub mod consts;
+pub mod state;
+pub mod state_machine;
+pub mod systems;
+
+use self::consts::*;
+use components::*;
+use layers;
+use player::{PLAYER_PROFILE, Player, ProfileWatcher};
+use specs::World;
+use util::Vector2;
+
+pub const ENEMY_PROFILE: &str = "movement/enemy.json";
+
+pub struct Enemy;
+impl Enemy {
+    /// A smaller, slower copy of the player that patrols and attacks on its own.
+    pub fn spawn(world: &mut World, location: Vector2) {
+        let profile = {
+            let watcher = world.read_resource::<ProfileWatcher>();
+            let mut profile = watcher.profile(ENEMY_PROFILE);
+            if profile.source.is_none() {
+                profile = watcher.profile(PLAYER_PROFILE);
+            }
+            profile
+        };
+
+        Player::actor(world, location, 0.3, layers::ENEMIES, profile)
+            .with(Abilities::none())
+            .with(Health::new(HEALTH))
+            .with(Damage {
+                amount: ATTACK_DAMAGE,
+                knockback: Vector2::new(ATTACK_KNOCKBACK_X, ATTACK_KNOCKBACK_Y),
+                invincibility: INVINCIBILITY_TIME,
+                hits: vec![layers::PLAYERS],
+            })
+            .with(Brain { machine: state_machine::StateMachine::new(state::Patrol) })
+            .build();
+    }
+}
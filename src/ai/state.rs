@@ -0,0 +1,233 @@
+use ai::consts as AC;
+use ai::state_machine::*;
+use components::*;
+use level::Terrain;
+use resources::*;
+use util::Vector2;
+
+/// Walks back and forth, turning around at walls and before stepping off a ledge.
+pub struct Patrol;
+
+impl State for Patrol {
+    fn update(
+        &mut self,
+        mv: &MovingObject,
+        bb: &HasAABB,
+        dir: &Directional,
+        target: &Option<Vector2>,
+        terrain: &Terrain,
+        input: &mut InputState,
+        _delta: &DeltaTime,
+    ) -> Trans {
+        if let Some(t) = *target {
+            if within(mv.position, t, AC::SIGHT_RANGE) {
+                return Trans::Push(Box::new(Chase));
+            }
+        }
+
+        let left = facing_left(dir);
+        let left = if path_blocked(mv, bb, left, terrain) {
+            !left
+        } else {
+            left
+        };
+        press(input, left);
+        Trans::None
+    }
+}
+
+/// Runs at the target until it's close enough to hit, stopping at ledges.
+pub struct Chase;
+
+impl State for Chase {
+    fn update(
+        &mut self,
+        mv: &MovingObject,
+        bb: &HasAABB,
+        dir: &Directional,
+        target: &Option<Vector2>,
+        terrain: &Terrain,
+        input: &mut InputState,
+        _delta: &DeltaTime,
+    ) -> Trans {
+        let t = match *target {
+            Some(t) if within(mv.position, t, AC::LOSE_RANGE) => t,
+            _ => return Trans::Pop,
+        };
+
+        let left = t.x < mv.position.x;
+        let close = within(mv.position, t, AC::ATTACK_RANGE);
+        if close && facing_left(dir) == left {
+            Trans::Push(Box::new(Attack {
+                cooldown: AC::ATTACK_COOLDOWN,
+            }))
+        } else if close || !path_blocked(mv, bb, left, terrain) {
+            press(input, left);
+            Trans::None
+        } else {
+            Trans::None
+        }
+    }
+}
+
+/// Swings once and waits for the cooldown before doing anything else.
+pub struct Attack {
+    pub cooldown: f64,
+}
+
+impl State for Attack {
+    fn on_start(
+        &mut self,
+        _mv: &MovingObject,
+        _bb: &HasAABB,
+        _dir: &Directional,
+        _target: &Option<Vector2>,
+        _terrain: &Terrain,
+        input: &mut InputState,
+        _delta: &DeltaTime,
+    ) {
+        input.left = false;
+        input.right = false;
+        input.attack = true;
+    }
+
+    fn update(
+        &mut self,
+        _mv: &MovingObject,
+        _bb: &HasAABB,
+        _dir: &Directional,
+        _target: &Option<Vector2>,
+        _terrain: &Terrain,
+        _input: &mut InputState,
+        delta: &DeltaTime,
+    ) -> Trans {
+        self.cooldown -= delta.delta;
+        if self.cooldown <= 0.0 {
+            Trans::Pop
+        } else {
+            Trans::None
+        }
+    }
+}
+
+fn within(from: Vector2, to: Vector2, range: (f64, f64)) -> bool {
+    (to.x - from.x).abs() <= range.0 && (to.y - from.y).abs() <= range.1
+}
+
+fn facing_left(dir: &Directional) -> bool {
+    match *dir {
+        Directional::Left => true,
+        Directional::Right => false,
+    }
+}
+
+fn press(input: &mut InputState, left: bool) {
+    input.left = left;
+    input.right = !left;
+}
+
+/// Whether walking on would run into a wall or off the floor under the feet.
+pub fn path_blocked(mv: &MovingObject, bb: &HasAABB, left: bool, terrain: &Terrain) -> bool {
+    if (left && bb.pushes_left_wall) || (!left && bb.pushes_right_wall) {
+        return true;
+    }
+
+    let side = if left { -1.0 } else { 1.0 };
+    let ahead_x = mv.position.x + bb.aabb.offset.x + side * (bb.aabb.half_size.x + 1.0);
+    let foot_y = mv.position.y + bb.aabb.offset.y - bb.aabb.half_size.y;
+
+    let x = terrain.get_tile_x_at_point(ahead_x);
+    let below = terrain.get_tile_y_at_point(foot_y - 1.0);
+    let level = terrain.get_tile_y_at_point(foot_y + 1.0);
+
+    let floor = terrain.is_ground(x, below) || terrain.is_ladder_top(x, below) ||
+        terrain.get_slope(x, below).is_some() || terrain.get_slope(x, level).is_some();
+    !floor || terrain.is_obstacle(x, level)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use level::TileType;
+    use physics::AABB;
+
+    // floor three tiles wide with a drop on the right, its edge is at x = 320
+    fn terrain() -> Terrain {
+        let mut rows = vec![vec![TileType::Empty; 5]; 4];
+        for x in 0..3 {
+            rows[0][x] = TileType::Block;
+        }
+        Terrain {
+            terrain: rows,
+            position: Vector2::new(0.0, 128.0),
+            width: 5,
+            height: 4,
+            tile_size: 128.0,
+        }
+    }
+
+    // standing on the floor, whose top is at 192
+    fn standing_at(x: f64) -> (MovingObject, HasAABB) {
+        let bb = HasAABB::new(AABB::new_full(
+            Vector2::new(64.0, 64.0),
+            Vector2::new(1.0, 1.0),
+        ));
+        (MovingObject::new(Vector2::new(x, 224.0)), bb)
+    }
+
+    fn patrol(x: f64, dir: Directional, target: Option<Vector2>) -> (InputState, Trans) {
+        let (mv, bb) = standing_at(x);
+        let mut input = InputState::new();
+        let trans = Patrol.update(
+            &mv,
+            &bb,
+            &dir,
+            &target,
+            &terrain(),
+            &mut input,
+            &DeltaTime::new(),
+        );
+        (input, trans)
+    }
+
+    #[test]
+    fn turns_around_before_the_ledge() {
+        let (mv, bb) = standing_at(128.0);
+        assert!(!path_blocked(&mv, &bb, false, &terrain()));
+        let (mv, bb) = standing_at(300.0);
+        assert!(path_blocked(&mv, &bb, false, &terrain()));
+        assert!(!path_blocked(&mv, &bb, true, &terrain()));
+
+        let (input, _) = patrol(128.0, Directional::Right, None);
+        assert!(input.right && !input.left);
+        let (input, _) = patrol(300.0, Directional::Right, None);
+        assert!(input.left && !input.right);
+    }
+
+    #[test]
+    fn chases_and_attacks_a_target_in_sight() {
+        match patrol(128.0, Directional::Right, Some(Vector2::new(500.0, 224.0))).1 {
+            Trans::Push(_) => (),
+            _ => panic!("should have noticed the target"),
+        }
+
+        let (mv, bb) = standing_at(128.0);
+        let mut input = InputState::new();
+        let target = Some(Vector2::new(200.0, 224.0));
+        let delta = DeltaTime::new();
+        match Chase.update(&mv, &bb, &Directional::Left, &target, &terrain(), &mut input, &delta) {
+            Trans::None => assert!(input.right),
+            _ => panic!("should turn toward the target first"),
+        }
+        match Chase.update(&mv, &bb, &Directional::Right, &target, &terrain(), &mut input, &delta) {
+            Trans::Push(_) => (),
+            _ => panic!("should attack"),
+        }
+
+        let far = Some(Vector2::new(128.0 + AC::LOSE_RANGE.0 * 2.0, 224.0));
+        match Chase.update(&mv, &bb, &Directional::Right, &far, &terrain(), &mut input, &delta) {
+            Trans::Pop => (),
+            _ => panic!("should give up"),
+        }
+    }
+}
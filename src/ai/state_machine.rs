@@ -0,0 +1,16 @@
+use components::*;
+use level::Terrain;
+use resources::*;
+use util::Vector2;
+
+state_machine!(
+    StateMachine; 
+    State; 
+    _mv: &MovingObject,
+    _bb: &HasAABB,
+    _dir: &Directional,
+    _target: &Option<Vector2>,
+    _terrain: &Terrain,
+    _input: &mut InputState,
+    _delta: &DeltaTime
+);
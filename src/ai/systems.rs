@@ -0,0 +1,54 @@
+use components::*;
+use rayon::iter::ParallelIterator;
+use resources::*;
+use specs::*;
+use util::Vector2;
+
+/// Lets every brain look for the nearest living player and press its buttons for the frame.
+pub struct BrainSystem;
+impl<'a> System<'a> for BrainSystem {
+    type SystemData = (
+        ReadStorage<'a, Controlled>,
+        ReadStorage<'a, Health>,
+        ReadStorage<'a, MovingObject>,
+        ReadStorage<'a, HasAABB>,
+        ReadStorage<'a, Directional>,
+        WriteStorage<'a, Brain>,
+        WriteStorage<'a, InputState>,
+        Fetch<'a, LevelTerrain>,
+        Fetch<'a, DeltaTime>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (controlled, health, mv, bb, dir, mut brain, mut input, level, time) = data;
+        let terrain = &level.terrain;
+
+        let players = (&controlled, &health, &mv)
+            .join()
+            .filter(|&(_, h, _)| !h.is_dead())
+            .map(|(_, _, mv)| mv.position)
+            .collect::<Vec<_>>();
+
+        (&mut brain, &mut input, &mv, &bb, &dir, &health).par_join().for_each(
+            |(brain, input, mv, bb, dir, health)| {
+                *input = InputState::new();
+                if health.is_dead() {
+                    return;
+                }
+
+                let target = nearest(&players, mv.position);
+                if !brain.machine.is_running() {
+                    brain.machine.start(mv, bb, dir, &target, terrain, input, &*time);
+                }
+                brain.machine.update(mv, bb, dir, &target, terrain, input, &*time);
+            },
+        );
+    }
+}
+
+fn nearest(positions: &[Vector2], to: Vector2) -> Option<Vector2> {
+    positions.iter().cloned().fold(None, |best: Option<Vector2>, p| match best {
+        Some(b) if (b - to).norm() <= (p - to).norm() => Some(b),
+        _ => Some(p),
+    })
+}
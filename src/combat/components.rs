@@ -0,0 +1,72 @@
+use specs::*;
+use util::Vector2;
+
+#[derive(Debug, Component, Clone)]
+#[component(DenseVecStorage)]
+pub struct Health {
+    pub current: f64,
+    pub max: f64,
+    /// Seconds left during which hits are ignored.
+    pub invincible_for: f64,
+}
+
+impl Health {
+    pub fn new(max: f64) -> Health {
+        Health {
+            current: max,
+            max,
+            invincible_for: 0.0,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    pub fn can_be_hit(&self) -> bool {
+        !self.is_dead() && self.invincible_for <= 0.0
+    }
+
+    pub fn kill(&mut self) {
+        self.current = 0.0;
+    }
+
+    /// Takes the damage and starts the invincibility frames, returns whether it was lethal.
+    pub fn hit(&mut self, damage: &Damage) -> bool {
+        self.current = (self.current - damage.amount).max(0.0);
+        self.invincible_for = damage.invincibility;
+        self.is_dead()
+    }
+}
+
+/// What an entity's attack hitboxes do to whoever they overlap.
+#[derive(Debug, Component, Clone)]
+#[component(DenseVecStorage)]
+pub struct Damage {
+    pub amount: f64,
+    /// Velocity given to the target, x points away from the attacker.
+    pub knockback: Vector2,
+    /// Seconds the target can't be hit again.
+    pub invincibility: f64,
+    /// Collision layers of the bodies it hurts, everyone else is left alone.
+    pub hits: Vec<usize>,
+}
+
+impl Damage {
+    pub fn knockback_from(&self, attacker: Vector2, target: Vector2) -> Vector2 {
+        let side = if target.x < attacker.x { -1.0 } else { 1.0 };
+        Vector2::new(side * self.knockback.x, self.knockback.y)
+    }
+}
+
+/// Marks an entity whose health just ran out, the death is handled elsewhere.
+#[derive(Debug, Component, Default)]
+#[component(NullStorage)]
+pub struct Killed;
+
+/// Seconds until a dead player is back on its feet at the level spawn.
+#[derive(Debug, Component)]
+#[component(HashMapStorage)]
+pub struct Respawn {
+    pub left: f64,
+}
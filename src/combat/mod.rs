@@ -0,0 +1,27 @@
+pub mod components;
+pub mod systems;
+
+use marker::SpriteData;
+use physics::Volume;
+
+/// Turns the hitboxes tagged on each sprite frame, given in frame pixels from the
+/// top left corner, into volumes around the sprite center with y pointing up.
+pub fn frame_hitboxes(data: &[SpriteData]) -> Vec<Vec<Volume>> {
+    data.iter()
+        .map(|sprite| {
+            let (w, h) = (sprite.frame.w as f64, sprite.frame.h as f64);
+            sprite
+                .hitboxes
+                .iter()
+                .map(|hb| {
+                    Volume::new(
+                        hb.x as f64 - w / 2.0,
+                        h / 2.0 - (hb.y + hb.h) as f64,
+                        hb.w as f64,
+                        hb.h as f64,
+                    )
+                })
+                .collect()
+        })
+        .collect()
+}
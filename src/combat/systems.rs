@@ -0,0 +1,252 @@
+use components::*;
+use physics::{Positioned, QuadTree, Volume};
+use rayon::iter::ParallelIterator;
+use resources::*;
+use specs::*;
+use util::Vector2;
+
+/// Checks attack hitboxes of the current animation frames against everyone's hurtbox,
+/// the AABB, and applies damage, knockback and invincibility frames.
+pub struct DamageSystem;
+impl<'a> System<'a> for DamageSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Renderable>,
+        ReadStorage<'a, Scalable>,
+        ReadStorage<'a, Directional>,
+        ReadStorage<'a, Damage>,
+        WriteStorage<'a, HasAABB>,
+        ReadStorage<'a, CollisionDetection>,
+        WriteStorage<'a, MovingObject>,
+        WriteStorage<'a, Health>,
+        WriteStorage<'a, Killed>,
+        Fetch<'a, AttackHitboxes>,
+        Fetch<'a, DeltaTime>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (
+            e,
+            rend,
+            scalable,
+            directional,
+            damage,
+            mut bb,
+            cd,
+            mut mv,
+            mut health,
+            mut killed,
+            hitboxes,
+            time,
+        ) = data;
+
+        (&mut health).par_join().for_each(|h| {
+            h.invincible_for = (h.invincible_for - time.delta).max(0.0);
+        });
+
+        let hurtboxes = (&*e, &mv, &bb, &health)
+            .join()
+            .filter(|&(_, _, _, h)| h.can_be_hit())
+            .map(|(e, mv, bb, _)| (e, (mv, bb).to_rect()))
+            .collect::<Vec<_>>();
+
+        if hurtboxes.is_empty() {
+            return;
+        }
+
+        let mut qt = QuadTree::new(enclosing(&hurtboxes));
+        for &(e, rect) in hurtboxes.iter() {
+            qt.insert(e, rect);
+        }
+
+        let mut hits: Vec<(Vector2, Entity, Damage)> = vec![];
+
+        for (attacker, rend, mv, dmg) in (&*e, &rend, &mv, &damage).join() {
+            let (id, frame) = match rend.tpe {
+                RenderableType::Animation { id, frame, .. } => (id, frame),
+                _ => continue,
+            };
+            let scale = scalable.get(attacker).cloned().unwrap_or(Scalable::new(1.0, 1.0));
+            let left = match directional.get(attacker) {
+                Some(&Directional::Left) => true,
+                _ => false,
+            };
+
+            for hitbox in hitboxes.get(id, frame) {
+                let vol = place_hitbox(hitbox, mv.position, &scale, left);
+
+                for (target, _) in qt.query_rect(vol) {
+                    let hurts = cd.get(target).map_or(false, |cd| dmg.hits.contains(&cd.layer));
+                    if hurts && target != attacker && !hits.iter().any(|&(_, t, _)| t == target) {
+                        hits.push((mv.position, target, dmg.clone()));
+                    }
+                }
+            }
+        }
+
+        for (from, target, dmg) in hits {
+            let h = match health.get_mut(target) {
+                Some(h) => h,
+                None => continue,
+            };
+            // two attackers landing on the same frame, the first one wins
+            if !h.can_be_hit() {
+                continue;
+            }
+            if h.hit(&dmg) {
+                killed.insert(target, Killed);
+            }
+            if let Some(mv) = mv.get_mut(target) {
+                mv.velocity = dmg.knockback_from(from, mv.position);
+            }
+            // knocked into the air, letting go of jump no longer cuts the rise
+            if let Some(bb) = bb.get_mut(target) {
+                bb.input_jump = false;
+            }
+        }
+    }
+}
+
+/// Puts a sprite-relative hitbox into the world, mirrored when the sprite faces left.
+pub fn place_hitbox(hitbox: &Volume, position: Vector2, scale: &Scalable, left: bool) -> Volume {
+    let (sx, sy) = (scale.x as f64, scale.y as f64);
+    let w = hitbox.w * sx;
+    let x = if left {
+        -(hitbox.x * sx + w)
+    } else {
+        hitbox.x * sx
+    };
+
+    Volume::new(
+        position.x + x,
+        position.y + hitbox.y * sy,
+        w,
+        hitbox.h * sy,
+    )
+}
+
+fn enclosing(volumes: &[(Entity, Volume)]) -> Volume {
+    let (mut x0, mut y0) = (::std::f64::MAX, ::std::f64::MAX);
+    let (mut x1, mut y1) = (::std::f64::MIN, ::std::f64::MIN);
+    for &(_, v) in volumes {
+        x0 = x0.min(v.x);
+        y0 = y0.min(v.y);
+        x1 = x1.max(v.x + v.w);
+        y1 = y1.max(v.y + v.h);
+    }
+    Volume::new(x0, y0, x1 - x0, y1 - y0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use physics::AABB;
+
+    fn arena(facing: Directional, target_x: f64) -> (World, Entity) {
+        let mut world = World::new();
+        register_components(&mut world);
+
+        let mut hitboxes = AttackHitboxes::new();
+        hitboxes.insert("attack", vec![vec![Volume::new(0.0, -20.0, 100.0, 40.0)]]);
+        world.add_resource(hitboxes);
+        world.add_resource(DeltaTime {
+            delta: 0.1,
+            now: 0.0,
+        });
+
+        world
+            .create_entity()
+            .with(MovingObject::new(Vector2::new(0.0, 0.0)))
+            .with(Renderable {
+                layer: 0,
+                tpe: RenderableType::Animation {
+                    id: "attack",
+                    frame: 0,
+                    length: 1,
+                },
+            })
+            .with(facing)
+            .with(Damage {
+                amount: 25.0,
+                knockback: Vector2::new(500.0, 300.0),
+                invincibility: 0.5,
+                hits: vec![1],
+            })
+            .build();
+
+        let target = world
+            .create_entity()
+            .with(MovingObject::new(Vector2::new(target_x, 0.0)))
+            .with(HasAABB::new(AABB::new_full(
+                Vector2::new(40.0, 40.0),
+                Vector2::new(1.0, 1.0),
+            )))
+            .with(Health::new(100.0))
+            .with(CollisionDetection { layer: 1, mass: 1.0 })
+            .build();
+
+        (world, target)
+    }
+
+    fn health(world: &World, e: Entity) -> f64 {
+        world.read::<Health>().get(e).unwrap().current
+    }
+
+    #[test]
+    fn hitbox_mirrors_when_facing_left() {
+        let hitbox = Volume::new(10.0, -5.0, 30.0, 20.0);
+        let scale = Scalable::new(0.5, 0.5);
+        let right = place_hitbox(&hitbox, Vector2::new(100.0, 0.0), &scale, false);
+        let left = place_hitbox(&hitbox, Vector2::new(100.0, 0.0), &scale, true);
+        assert_eq!((right.x, right.y, right.w, right.h), (105.0, -2.5, 15.0, 10.0));
+        assert_eq!((left.x, left.y, left.w, left.h), (80.0, -2.5, 15.0, 10.0));
+    }
+
+    #[test]
+    fn hits_once_then_target_is_invincible() {
+        let (mut world, target) = arena(Directional::Right, 100.0);
+        DamageSystem.run_now(&mut world.res);
+        assert_eq!(health(&world, target), 75.0);
+        assert_eq!(
+            world.read::<MovingObject>().get(target).unwrap().velocity,
+            Vector2::new(500.0, 300.0)
+        );
+
+        DamageSystem.run_now(&mut world.res);
+        assert_eq!(health(&world, target), 75.0);
+
+        for _ in 0..5 {
+            DamageSystem.run_now(&mut world.res);
+        }
+        assert_eq!(health(&world, target), 50.0);
+    }
+
+    #[test]
+    fn only_hits_in_front() {
+        let (mut world, target) = arena(Directional::Right, -100.0);
+        DamageSystem.run_now(&mut world.res);
+        assert_eq!(health(&world, target), 100.0);
+
+        let (mut world, target) = arena(Directional::Left, -100.0);
+        DamageSystem.run_now(&mut world.res);
+        assert_eq!(health(&world, target), 75.0);
+        assert!(world.read::<MovingObject>().get(target).unwrap().velocity.x < 0.0);
+    }
+
+    #[test]
+    fn only_hits_the_layers_it_is_meant_for() {
+        let (mut world, target) = arena(Directional::Right, 100.0);
+        world.write::<CollisionDetection>().get_mut(target).unwrap().layer = 0;
+        DamageSystem.run_now(&mut world.res);
+        assert_eq!(health(&world, target), 100.0);
+    }
+
+    #[test]
+    fn lethal_hit_marks_the_target() {
+        let (mut world, target) = arena(Directional::Right, 100.0);
+        world.write::<Health>().get_mut(target).unwrap().current = 10.0;
+        DamageSystem.run_now(&mut world.res);
+        assert!(world.read::<Health>().get(target).unwrap().is_dead());
+        assert!(world.read::<Killed>().get(target).is_some());
+    }
+}
@@ -1,7 +1,10 @@
+pub use ai::components::*;
+pub use combat::components::*;
 pub use physics::components::*;
 pub use player::components::*;
 pub use player::components::*;
 pub use rendering::animation_seq::*;
+use input::Action;
 use specs::*;
 
 
@@ -51,9 +54,142 @@ pub struct Renderable {
     pub tpe: RenderableType,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum InputDevice {
+    Keyboard,
+    /// A game controller by its `instance_id`.
+    Controller(i32),
+}
+
+/// Which device drives the entity's `InputState`.
 #[derive(Debug, Component)]
 #[component(DenseVecStorage)]
-pub struct Controlled;
+pub struct Controlled {
+    pub device: InputDevice,
+}
+
+/// Buttons held by one device or one entity, the state machines only read the entity's.
+#[derive(Debug, Component, Clone, Serialize, Deserialize)]
+#[component(DenseVecStorage)]
+pub struct InputState {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+    pub slide: bool,
+    pub attack: bool,
+    /// How far the stick is pushed sideways, 1.0 for keys and the d-pad.
+    pub walk_scale: f64,
+
+    pub jump_held: bool,
+    pub jump_pressed_at: Option<f64>,
+    pub jump_released_at: Option<f64>,
+
+    // actions held by keys and buttons and actions held by a stick, one bit per action,
+    // kept apart so a centred stick doesn't let go of a held d-pad
+    #[serde(default)]
+    buttons: u8,
+    #[serde(default)]
+    axes: u8,
+}
+
+impl InputState {
+    pub fn new() -> InputState {
+        InputState {
+            up: false,
+            down: false,
+            left: false,
+            right: false,
+            slide: false,
+            attack: false,
+            walk_scale: 1.0,
+            jump_held: false,
+            jump_pressed_at: None,
+            jump_released_at: None,
+            buttons: 0,
+            axes: 0,
+        }
+    }
+
+    pub fn press_jump(&mut self, now: f64) {
+        if !self.jump_held {
+            self.jump_held = true;
+            self.jump_pressed_at = Some(now);
+        }
+    }
+
+    pub fn release_jump(&mut self, now: f64) {
+        if self.jump_held {
+            self.jump_held = false;
+            self.jump_released_at = Some(now);
+        }
+    }
+
+    /// The last jump press, if it happened at most `window` seconds ago.
+    pub fn jump_pressed_within(&self, now: f64, window: f64) -> Option<f64> {
+        match self.jump_pressed_at {
+            Some(t) if now - t <= window => Some(t),
+            _ => None,
+        }
+    }
+
+    /// Presses or releases a key or button bound to the action, slide and attack only
+    /// trigger on the press.
+    pub fn set(&mut self, action: Action, pressed: bool, now: f64) {
+        let was_held = self.holds(action);
+        self.buttons = with_bit(self.buttons, action, pressed);
+        if pressed && (action == Action::Left || action == Action::Right) {
+            self.walk_scale = 1.0;
+        }
+        self.apply(action, was_held, now);
+    }
+
+    /// Like `set`, but from an axis pushed `amount` of the way out, 0.0 releases.
+    /// The action stays held while a button still holds it.
+    pub fn set_axis(&mut self, action: Action, amount: f64, now: f64) {
+        let was_held = self.holds(action);
+        self.axes = with_bit(self.axes, action, amount > 0.0);
+        if action == Action::Left || action == Action::Right {
+            if amount > 0.0 {
+                self.walk_scale = amount;
+            } else if self.buttons & bit(action) != 0 {
+                self.walk_scale = 1.0;
+            }
+        }
+        self.apply(action, was_held, now);
+    }
+
+    fn holds(&self, action: Action) -> bool {
+        (self.buttons | self.axes) & bit(action) != 0
+    }
+
+    fn apply(&mut self, action: Action, was_held: bool, now: f64) {
+        let held = self.holds(action);
+        match action {
+            Action::Left => self.left = held,
+            Action::Right => self.right = held,
+            Action::Up => self.up = held,
+            Action::Down => self.down = held,
+            Action::Jump if held => self.press_jump(now),
+            Action::Jump => self.release_jump(now),
+            Action::Slide => self.slide |= held && !was_held,
+            Action::Attack => self.attack |= held && !was_held,
+        }
+    }
+
+    pub fn reset_actions(&mut self) {
+        self.attack = false;
+        self.slide = false;
+    }
+}
+
+fn bit(action: Action) -> u8 {
+    1 << action as u8
+}
+
+fn with_bit(bits: u8, action: Action, set: bool) -> u8 {
+    if set { bits | bit(action) } else { bits & !bit(action) }
+}
 
 #[derive(Debug, Component, Default)]
 #[component(NullStorage)]
@@ -95,6 +231,15 @@ pub fn register_components(world: &mut World) {
     world.register::<Controlled>();
     world.register::<SnapCamera>();
     world.register::<StartPSM>();
+    world.register::<Abilities>();
+    world.register::<MovementProfile>();
+    world.register::<Health>();
+    world.register::<Damage>();
+    world.register::<Killed>();
+    world.register::<Respawn>();
+    world.register::<InputState>();
+    world.register::<Brain>();
     world.register::<ChaseCamera>();
     world.register::<CollisionDetection>();
+    world.register::<Trigger>();
 }
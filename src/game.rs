@@ -2,12 +2,20 @@ use ggez::*;
 use ggez::event::*;
 use level::*;
 
+use ai::{ENEMY_PROFILE, Enemy};
 use asset_storage::*;
 use camera::*;
+use input::{Action, BINDINGS, Bindings};
+use layers;
+use physics::SpatialGrid;
+use rand;
+use replay::*;
 use components::*;
 use player::*;
 use resources::*;
 use specs::*;
+use std::fs;
+use std::path::PathBuf;
 use std::time::Duration;
 use systems::*;
 
@@ -16,36 +24,58 @@ use util::{Vector2, seconds};
 pub struct Game<'a, 'b> {
     pub world: World,
     pub player_count: usize,
+    /// Runs one simulation step, everything that doesn't only exist for drawing.
     pub dispatcher: Dispatcher<'a, 'b>,
+    pub clock: FixedStep,
+    pub levels: Vec<LevelType>,
+    pub current_level: usize,
+    pub next_level: Option<usize>,
+    pub session: Session,
+    /// Joins and spawns since the last frame, kept for the recording.
+    pub events: Vec<ReplayEvent>,
+    pub recording_requested: bool,
+    pub playback_requested: bool,
 }
 
 impl<'a, 'b> Game<'a, 'b> {
-    pub fn new(ctx: &mut Context) -> GameResult<Game<'a, 'b>> {
+    pub fn new(ctx: &mut Context, levels: Vec<LevelType>) -> GameResult<Game<'a, 'b>> {
         let mut world = World::new();
         let mut pc = 0;
+        let spawn;
+        let triggers;
         register_components(&mut world);
 
+        if levels.is_empty() {
+            return Err(GameError::ResourceLoadError("No levels found".to_owned()));
+        }
+
         //load everything!
         {
             let mut asset_storage = AssetStorage::empty();
 
-            //level part
-            {
-                let level = Level::load(ctx, LevelType::Graveyard)?;
-                let RenderableLevel {
-                    background,
-                    ground_batch,
-                    objects_batch,
-                    terrain,
-                } = RenderableLevel::build(level);
-                asset_storage.images.insert("level-background", background);
-                asset_storage.batches.insert("level-ground", ground_batch);
-                asset_storage.batches.insert("level-objects", objects_batch);
-                world.add_resource(LevelTerrain { terrain });
-            }
             //player part
+            let profile = {
+                let mut hitboxes = AttackHitboxes::new();
+                AnimationLoader::load_assets(ctx, &mut asset_storage, &mut hitboxes)?;
+                world.add_resource(hitboxes);
+
+                let resources = ctx.filesystem.get_resources_dir().to_owned();
+                let mut watcher = ProfileWatcher::new(resources.clone());
+                let profile = watcher.watch(PLAYER_PROFILE)?;
+                watcher.watch(ENEMY_PROFILE)?;
+                world.add_resource(watcher);
+
+                world.add_resource(Bindings::load(resources.join(BINDINGS))?);
+                profile
+            };
+            //level part
             {
-                AnimationLoader::load_assets(ctx, &mut asset_storage)?;
+                let (terrain, s, t) =
+                    Game::build_level(ctx, &mut asset_storage, &levels[0], &profile)?;
+                spawn = s;
+                triggers = t;
+                world.add_resource(SpatialGrid::new(terrain.tile_size, CONTACT_SKIN));
+                world.add_resource(LevelTerrain { terrain, spawn });
             }
             world.add_resource::<AssetStorage>(asset_storage);
         }
@@ -54,11 +84,20 @@ impl<'a, 'b> Game<'a, 'b> {
             .create_entity()
             .with(Position::new(0.0, 0.0))
             .with(Renderable {
-                layer: 1,
+                layer: 2,
                 tpe: RenderableType::Batch { id: "level-ground" },
             })
             .build();
 
+        world
+            .create_entity()
+            .with(Position::new(0.0, 0.0))
+            .with(Renderable {
+                layer: 1,
+                tpe: RenderableType::Batch { id: "level-objects" },
+            })
+            .build();
+
         world
             .create_entity()
             .with(Position::new(0.0, 0.0))
@@ -71,8 +110,14 @@ impl<'a, 'b> Game<'a, 'b> {
             .build();
 
         world.add_resource(MousePointer(0.0, 0.0));
-        world.add_resource(DeltaTime { delta: 0.0 });
-        world.add_resource(PlayerInput::new());
+        world.add_resource(DeltaTime::new());
+        world.add_resource(JumpTuning::new());
+        world.add_resource(InputDevices::new());
+        world.add_resource(RandomSeed(rand::random()));
+        world.add_resource(CollisionEvents::new());
+        world.add_resource(TriggerEvents::new());
+        world.add_resource(CameraZone(None));
+        world.add_resource(layers::collision_layers());
 
         let (w, h) = (ctx.conf.window_width, ctx.conf.window_height);
         let hc = h as f64 / w as f64;
@@ -80,11 +125,33 @@ impl<'a, 'b> Game<'a, 'b> {
 
         world.add_resource(Camera::new(w, h, fov, hc * fov));
 
-        Player::spawn(&mut world, Vector2::new(500.0, 500.0), true, true, &mut pc);
+        Player::spawn(&mut world, spawn, Some(InputDevice::Keyboard), true, &mut pc);
+        Game::spawn_triggers(&mut world, triggers);
+
 
-        let dispatcher: Dispatcher<'a, 'b> = DispatcherBuilder::new()
-            .add(StartPSMSystem, "start-state-machines", &[])
-            .add(PlayerDirectionSystem, "p.direct", &[])
+        Ok(Game {
+            world,
+            player_count: pc,
+            dispatcher: Game::simulation(),
+            clock: FixedStep::new(),
+            levels,
+            current_level: 0,
+            next_level: None,
+            session: Session::Live,
+            events: vec![],
+            recording_requested: false,
+            playback_requested: false,
+        })
+    }
+
+    /// Builds the `dispatcher`.
+    pub fn simulation() -> Dispatcher<'a, 'b> {
+        DispatcherBuilder::new()
+            .add(ProfileReloadSystem, "profile_reload", &[])
+            .add(StartPSMSystem, "start-state-machines", &["profile_reload"])
+            .add(HumanInputSystem, "human_input", &[])
+            .add(BrainSystem, "ai", &[])
+            .add(PlayerDirectionSystem, "p.direct", &["human_input", "ai"])
             .add(
                 PlayerHandleEventsSystem,
                 "p.handle_events",
@@ -97,40 +164,299 @@ impl<'a, 'b> Game<'a, 'b> {
                 "p.update",
                 &["p.handle_events"],
             )
-            .add(PositionSystem, "position", &["has_aabb"])
+            .add(HazardSystem, "hazards", &["has_aabb"])
+            .add(DamageSystem, "damage", &["has_aabb", "p.update"])
             .add(
                 ResetInputSystem,
                 "p.reset_input",
-                &["p.handle_events"],
+                &["human_input", "p.handle_events"],
             )
             .add(CollisionSystem, "collisions", &["has_aabb"])
-            .add(CameraSnapSystem, "camera_snap", &["position"])
-            .add(ChaseCameraSystem, "chase_camera", &["position"])
-            .build();
+            .add(TriggerSystem, "triggers", &["collisions"])
+            .add(KillZoneSystem, "kill_zones", &["triggers"])
+            .add(CheckpointSystem, "checkpoints", &["triggers"])
+            .add(CameraZoneSystem, "camera_zones", &["triggers"])
+            .add(DeathSystem, "deaths", &["damage", "hazards", "kill_zones"])
+            .add(RespawnSystem, "respawns", &["deaths", "checkpoints"])
+            .build()
+    }
 
-        Ok(Game {
-            world,
-            player_count: pc,
-            dispatcher,
-        })
+    /// Runs one simulation step, the fixed update systems too when `fixed` is set.
+    pub fn simulate(world: &mut World, dispatcher: &mut Dispatcher, delta: f64, fixed: bool) {
+        world.write_resource::<DeltaTime>().advance(delta);
+
+        if fixed {
+            PlayerFixedUpdateSystem.run_now(&mut world.res);
+            AnimationFFSystem.run_now(&mut world.res);
+        }
+
+        dispatcher.dispatch(&mut world.res);
+        world.maintain();
+    }
+
+    fn build_level(
+        ctx: &mut Context,
+        asset_storage: &mut AssetStorage,
+        lt: &LevelType,
+        profile: &MovementProfile,
+    ) -> GameResult<(Terrain, Vector2, Vec<Trigger>)> {
+        let level = Level::load(ctx, lt, profile)?;
+        let RenderableLevel {
+            background,
+            ground_batch,
+            objects_batch,
+            terrain,
+            spawns,
+            triggers,
+        } = RenderableLevel::build(level);
+        let spawn = spawns.first().cloned().unwrap_or(Vector2::new(500.0, 500.0));
+        asset_storage.images.insert("level-background", background);
+        asset_storage.batches.insert("level-ground", ground_batch);
+        asset_storage.batches.insert("level-objects", objects_batch);
+        Ok((terrain, spawn, triggers))
+    }
+
+    fn spawn_triggers(world: &mut World, triggers: Vec<Trigger>) {
+        for trigger in triggers {
+            world.create_entity().with(trigger).build();
+        }
+    }
+
+    pub fn switch_level(&mut self, ctx: &mut Context, ix: usize) -> GameResult<()> {
+        let devices = self.controlled_devices();
+        self.load_level(ctx, ix, &devices)
+    }
+
+    /// Starts the level over with a player for each of the devices.
+    fn load_level(
+        &mut self,
+        ctx: &mut Context,
+        ix: usize,
+        devices: &[InputDevice],
+    ) -> GameResult<()> {
+        let lt = self.levels[ix].clone();
+        let profile = self.world.read_resource::<ProfileWatcher>().profile(PLAYER_PROFILE);
+        let (terrain, spawn, triggers) = {
+            let mut assets = self.world.write_resource::<AssetStorage>();
+            Game::build_level(ctx, &mut *assets, &lt, &profile)?
+        };
+        *self.world.write_resource::<SpatialGrid>() =
+            SpatialGrid::new(terrain.tile_size, CONTACT_SKIN);
+        {
+            let mut level = self.world.write_resource::<LevelTerrain>();
+            level.terrain = terrain;
+            level.spawn = spawn;
+        }
+
+        {
+            let entities = self.world.entities();
+            let mv = self.world.read::<MovingObject>();
+            let triggers = self.world.read::<Trigger>();
+            for (e, _) in (&*entities, &mv).join() {
+                entities.delete(e);
+            }
+            for (e, _) in (&*entities, &triggers).join() {
+                entities.delete(e);
+            }
+        }
+        self.world.maintain();
+        Game::spawn_triggers(&mut self.world, triggers);
+        self.world.write_resource::<CameraZone>().0 = None;
+
+        self.player_count = 0;
+        Player::spawn(
+            &mut self.world,
+            spawn,
+            Some(InputDevice::Keyboard),
+            true,
+            &mut self.player_count,
+        );
+        for &device in devices {
+            if device != InputDevice::Keyboard {
+                Player::spawn(&mut self.world, spawn, Some(device), false, &mut self.player_count);
+            }
+        }
+        self.current_level = ix;
+        println!("Level: {}", lt.name());
+        Ok(())
+    }
+
+    fn controlled_devices(&self) -> Vec<InputDevice> {
+        let controlled = self.world.read::<Controlled>();
+        controlled.join().map(|c| c.device).collect()
+    }
+
+    fn press(&self, device: InputDevice, actions: &[Action], pressed: bool) {
+        let now = self.world.read_resource::<DeltaTime>().now;
+        let mut devices = self.world.write_resource::<InputDevices>();
+        let input = devices.get_mut(device);
+        for &action in actions {
+            input.set(action, pressed, now);
+        }
+    }
+
+    /// A controller nobody plays with yet gets its own player at the level spawn.
+    fn join(&mut self, device: InputDevice) {
+        if self.controlled_devices().contains(&device) {
+            return;
+        }
+        let spawn = self.world.read_resource::<LevelTerrain>().spawn;
+        Player::spawn(&mut self.world, spawn, Some(device), false, &mut self.player_count);
+        self.events.push(ReplayEvent::Join(device));
+    }
+
+    /// Whether a player walked into a level exit during the last step.
+    fn reached_exit(&self) -> bool {
+        let controlled = self.world.read::<Controlled>();
+        self.world
+            .read_resource::<TriggerEvents>()
+            .entered(TriggerKind::Exit)
+            .iter()
+            .any(|&(_, e)| controlled.get(e).is_some())
+    }
+
+    fn playing(&self) -> bool {
+        match self.session {
+            Session::Playing(_) => true,
+            _ => false,
+        }
+    }
+
+    fn spawn_enemy(&mut self, at: Vector2) {
+        Enemy::spawn(&mut self.world, at);
+        self.events.push(ReplayEvent::spawn_enemy(at));
+    }
+
+    /// Delta and fixed update flag for the next simulation step. Recordings keep every
+    /// step, playback feeds the recorded input and events back in instead.
+    fn step(&mut self, fixed: bool) -> (f64, bool) {
+        let events = ::std::mem::replace(&mut self.events, vec![]);
+        let delta = self.clock.step;
+
+        let frame = match self.session {
+            Session::Live => None,
+            Session::Recording(ref mut replay) => {
+                let devices = self.world.read_resource::<InputDevices>().snapshot();
+                replay.frames.push(ReplayFrame {
+                    delta,
+                    fixed,
+                    devices,
+                    events,
+                });
+                return (delta, fixed);
+            }
+            Session::Playing(ref mut playback) => playback.next_frame(),
+        };
+
+        match frame {
+            Some(frame) => {
+                self.world.write_resource::<InputDevices>().restore(&frame.devices);
+                for event in frame.events {
+                    match event {
+                        ReplayEvent::Join(device) => self.join(device),
+                        ReplayEvent::SpawnEnemy(x, y) => self.spawn_enemy(Vector2::new(x, y)),
+                    }
+                }
+                self.events.clear();
+                (frame.delta, frame.fixed)
+            }
+            None => {
+                if self.playing() {
+                    println!("Replay finished");
+                    self.session = Session::Live;
+                }
+                (delta, fixed)
+            }
+        }
+    }
+
+    fn replay_path(ctx: &Context) -> PathBuf {
+        ctx.filesystem.get_user_data_dir().join(REPLAY)
+    }
+
+    /// Restarts the level with fresh time and randomness, so a replay starts from
+    /// exactly the same world as its recording.
+    fn restart(
+        &mut self,
+        ctx: &mut Context,
+        level: usize,
+        seed: u32,
+        devices: &[InputDevice],
+    ) -> GameResult<()> {
+        self.load_level(ctx, level, devices)?;
+        *self.world.write_resource::<DeltaTime>() = DeltaTime::new();
+        self.clock = FixedStep::new();
+        *self.world.write_resource::<RandomSeed>() = RandomSeed(seed);
+        self.world.write_resource::<InputDevices>().restore(&[]);
+        self.events.clear();
+        Ok(())
+    }
+
+    fn toggle_recording(&mut self, ctx: &mut Context) -> GameResult<()> {
+        match ::std::mem::replace(&mut self.session, Session::Live) {
+            Session::Recording(replay) => {
+                let path = Game::replay_path(ctx);
+                if let Some(dir) = path.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                replay.save(&path)?;
+                println!("Saved {} frames to {}", replay.frames.len(), path.display());
+            }
+            _ => {
+                let (level, seed) = (self.current_level, rand::random());
+                let devices = self.controlled_devices();
+                self.restart(ctx, level, seed, &devices)?;
+                self.session = Session::Recording(Replay::new(level, seed, devices));
+                println!("Recording");
+            }
+        }
+        Ok(())
+    }
+
+    fn start_playback(&mut self, ctx: &mut Context) -> GameResult<()> {
+        let replay = Replay::load(&Game::replay_path(ctx))?;
+        let devices = replay.devices.clone();
+        self.restart(ctx, replay.level, replay.seed, &devices)?;
+        self.session = Session::Playing(Playback::new(replay));
+        println!("Playing back");
+        Ok(())
     }
 }
 
 impl<'a, 'b> event::EventHandler for Game<'a, 'b> {
     fn update(&mut self, ctx: &mut Context, dt: Duration) -> GameResult<()> {
+        if let Some(ix) = self.next_level.take() {
+            self.switch_level(ctx, ix)?;
+        }
+
         if timer::get_ticks(ctx) % 100 == 0 {
             println!("FPS: {}", timer::get_fps(ctx));
         }
 
-        self.world.write_resource::<DeltaTime>().delta = seconds(&dt);
+        if self.recording_requested {
+            self.recording_requested = false;
+            self.toggle_recording(ctx)?;
+        }
+        if self.playback_requested {
+            self.playback_requested = false;
+            self.start_playback(ctx)?;
+        }
+
+        self.world.write_resource::<Bindings>().poll();
 
-        if timer::check_update_time(ctx, 30) {
-            PlayerFixedUpdateSystem.run_now(&mut self.world.res);
-            AnimationFFSystem.run_now(&mut self.world.res);
+        self.clock.add(seconds(&dt));
+        while let Some(fixed) = self.clock.next() {
+            let (delta, fixed) = self.step(fixed);
+            Game::simulate(&mut self.world, &mut self.dispatcher, delta, fixed);
+
+            if self.next_level.is_none() && self.reached_exit() {
+                self.next_level = Some((self.current_level + 1) % self.levels.len());
+            }
         }
 
-        self.dispatcher.dispatch(&mut self.world.res);
-        self.world.maintain();
+        PositionSystem { alpha: self.clock.alpha() }.run_now(&mut self.world.res);
+        CameraSnapSystem.run_now(&mut self.world.res);
+        ChaseCameraSystem.run_now(&mut self.world.res);
 
         Ok(())
     }
@@ -149,77 +475,57 @@ impl<'a, 'b> event::EventHandler for Game<'a, 'b> {
     }
 
     fn key_down_event(&mut self, keycode: Keycode, _keymod: Mod, repeat: bool) {
-        let mut input = self.world.write_resource::<PlayerInput>();
+        let level_count = self.levels.len();
+        match keycode {
+            Keycode::PageDown if !repeat => {
+                self.next_level = Some((self.current_level + 1) % level_count);
+            }
+            Keycode::PageUp if !repeat => {
+                self.next_level = Some((self.current_level + level_count - 1) % level_count);
+            }
+            Keycode::F9 if !repeat => self.recording_requested = true,
+            Keycode::F10 if !repeat => self.playback_requested = true,
+            _ => (),
+        }
 
         if !repeat {
-            match keycode {
-                Keycode::Left => input.left = true,
-                Keycode::Right => input.right = true,
-                Keycode::Up => input.up = true,
-                Keycode::Down => input.down = true,
-                Keycode::LCtrl => input.slide = true,
-                Keycode::Space => input.jump = true,
-                Keycode::LShift => input.attack = true,
-                _ => (),
-            }
+            let actions = self.world.read_resource::<Bindings>().key(keycode);
+            self.press(InputDevice::Keyboard, &actions, true);
         }
     }
 
     fn key_up_event(&mut self, keycode: Keycode, _keymod: Mod, repeat: bool) {
-        let mut input = self.world.write_resource::<PlayerInput>();
         if !repeat {
-            //wat?
-            match keycode {
-                Keycode::Left => input.left = false,
-                Keycode::Right => input.right = false,
-                Keycode::Up => input.up = false,
-                Keycode::Down => input.down = false,
-                _ => (),
-            }
+            let actions = self.world.read_resource::<Bindings>().key(keycode);
+            self.press(InputDevice::Keyboard, &actions, false);
         }
     }
 
-    fn controller_button_down_event(&mut self, btn: Button, _instance_id: i32) {
-        let mut input = self.world.write_resource::<PlayerInput>();
-        match btn {
-            Button::A => input.jump = true,
-            Button::X => input.attack = true,
-            Button::B => input.slide = true,
-            // Button::LeftShoulder => self.player.mv.position = Vector2::new(300.0, 500.0),
-            _ => (),
+    fn controller_button_down_event(&mut self, btn: Button, instance_id: i32) {
+        if !self.playing() {
+            self.join(InputDevice::Controller(instance_id));
         }
+        let actions = self.world.read_resource::<Bindings>().button(btn);
+        self.press(InputDevice::Controller(instance_id), &actions, true);
     }
-    fn controller_button_up_event(&mut self, _btn: Button, _instance_id: i32) {}
-    fn controller_axis_event(&mut self, axis: Axis, value: i16, _instance_id: i32) {
-        let mut input = self.world.write_resource::<PlayerInput>();
-        match axis {
-            Axis::LeftX => {
-                if value > 7500 {
-                    input.right = true
-                } else {
-                    input.right = false
-                };
-                if value < -7500 {
-                    input.left = true
-                } else {
-                    input.left = false
-                }
-            }
-            Axis::LeftY => {
-                if value > 7500 {
-                    input.down = true
-                } else {
-                    input.down = false
-                }
-            }
-            _ => (),
+    fn controller_button_up_event(&mut self, btn: Button, instance_id: i32) {
+        let actions = self.world.read_resource::<Bindings>().button(btn);
+        self.press(InputDevice::Controller(instance_id), &actions, false);
+    }
+    fn controller_axis_event(&mut self, axis: Axis, value: i16, instance_id: i32) {
+        let now = self.world.read_resource::<DeltaTime>().now;
+        let bindings = self.world.read_resource::<Bindings>();
+        let mut devices = self.world.write_resource::<InputDevices>();
+        let input = devices.get_mut(InputDevice::Controller(instance_id));
+        for (action, amount) in bindings.axis(axis, value) {
+            input.set_axis(action, amount, now);
         }
     }
 
     fn mouse_button_down_event(&mut self, button: event::MouseButton, x: i32, y: i32) {
-        if button == event::MouseButton::Left {
+        if button == event::MouseButton::Left && !self.playing() {
             let p = self.world.read_resource::<Camera>().screen_to_world_coords((x, y));
-            Player::spawn(&mut self.world, p, false, false, &mut self.player_count)
+            self.spawn_enemy(p)
         }
     }
 
@@ -231,8 +537,99 @@ impl<'a, 'b> event::EventHandler for Game<'a, 'b> {
     }
 
     fn mouse_wheel_event(&mut self, _: i32, _: i32) {
+        if self.playing() {
+            return;
+        }
         let mp = self.world.read_resource::<MousePointer>().clone();
         let p = Vector2::new(mp.0, mp.1);
-        Player::spawn(&mut self.world, p, false, false, &mut self.player_count);
+        self.spawn_enemy(p);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    // a 16x8 room with a floor, a player and an enemy on it
+    fn arena(seed: u32) -> World {
+        let mut world = World::new();
+        register_components(&mut world);
+
+        let mut rows = vec![vec![TileType::Empty; 16]; 8];
+        for x in 0..16 {
+            rows[0][x] = TileType::Block;
+        }
+        let terrain = Terrain {
+            terrain: rows,
+            position: Vector2::new(0.0, 128.0),
+            width: 16,
+            height: 8,
+            tile_size: 128.0,
+        };
+        let spawn = Vector2::new(400.0, 300.0);
+        world.add_resource(SpatialGrid::new(terrain.tile_size, CONTACT_SKIN));
+        world.add_resource(LevelTerrain { terrain, spawn });
+        world.add_resource(ProfileWatcher::new(env::temp_dir()));
+        world.add_resource(AttackHitboxes::new());
+        world.add_resource(DeltaTime::new());
+        world.add_resource(JumpTuning::new());
+        world.add_resource(InputDevices::new());
+        world.add_resource(RandomSeed(seed));
+        world.add_resource(CollisionEvents::new());
+        world.add_resource(TriggerEvents::new());
+        world.add_resource(CameraZone(None));
+        world.add_resource(layers::collision_layers());
+
+        let mut pc = 0;
+        Player::spawn(&mut world, spawn, Some(InputDevice::Keyboard), true, &mut pc);
+        Enemy::spawn(&mut world, Vector2::new(1200.0, 300.0));
+        world
+    }
+
+    fn positions(world: &World) -> Vec<(u32, Vector2)> {
+        let entities = world.entities();
+        let mv = world.read::<MovingObject>();
+        (&*entities, &mv).join().map(|(e, mv)| (e.id(), mv.position)).collect()
+    }
+
+    #[test]
+    fn playback_ends_up_where_the_recording_did() {
+        let mut world = arena(7);
+        let mut sim = Game::simulation();
+        let mut clock = FixedStep::new();
+        let mut replay = Replay::new(0, 7, vec![InputDevice::Keyboard]);
+
+        for i in 0..240 {
+            {
+                let now = world.read_resource::<DeltaTime>().now;
+                let mut devices = world.write_resource::<InputDevices>();
+                let kb = devices.get_mut(InputDevice::Keyboard);
+                kb.set(Action::Right, i >= 10 && i < 150, now);
+                kb.set(Action::Jump, i % 60 >= 30 && i % 60 < 40, now);
+                kb.set(Action::Attack, i == 100, now);
+            }
+            let step = clock.step;
+            clock.add(step);
+            let fixed = clock.next().unwrap();
+            replay.frames.push(ReplayFrame {
+                delta: step,
+                fixed,
+                devices: world.read_resource::<InputDevices>().snapshot(),
+                events: vec![],
+            });
+            Game::simulate(&mut world, &mut sim, step, fixed);
+        }
+        let recorded = positions(&world);
+        assert!(recorded[0].1.x > 500.0, "player never got going");
+
+        let mut world = arena(replay.seed);
+        let mut sim = Game::simulation();
+        let mut playback = Playback::new(replay);
+        while let Some(frame) = playback.next_frame() {
+            world.write_resource::<InputDevices>().restore(&frame.devices);
+            Game::simulate(&mut world, &mut sim, frame.delta, frame.fixed);
+        }
+        assert_eq!(positions(&world), recorded);
     }
 }
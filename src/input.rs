@@ -0,0 +1,297 @@
+use ggez::{GameError, GameResult};
+use ggez::event::{Axis, Button, Keycode};
+use serde_json;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+pub const BINDINGS: &str = "bindings.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Left,
+    Right,
+    Up,
+    Down,
+    Jump,
+    Slide,
+    Attack,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Binding {
+    Key(Keycode),
+    Button(Button),
+    /// Active while the axis sits outside its deadzone and within `min..=max`.
+    Axis { axis: Axis, min: i16, max: i16 },
+}
+
+#[derive(Deserialize)]
+struct BindingsFile {
+    #[serde(default = "default_deadzone")]
+    deadzone: i16,
+    #[serde(default)]
+    deadzones: HashMap<String, i16>,
+    actions: HashMap<Action, ActionFile>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct ActionFile {
+    keys: Vec<String>,
+    buttons: Vec<String>,
+    axes: Vec<AxisFile>,
+}
+
+#[derive(Deserialize)]
+struct AxisFile {
+    axis: String,
+    min: i16,
+    max: i16,
+}
+
+fn default_deadzone() -> i16 {
+    7500
+}
+
+/// Maps keys, gamepad buttons and axis ranges to game actions. Loaded from
+/// `bindings.json` in the resources dir and reloaded when that file is saved.
+pub struct Bindings {
+    bindings: Vec<(Binding, Action)>,
+    deadzone: i16,
+    deadzones: HashMap<Axis, i16>,
+    path: Option<PathBuf>,
+    modified: Option<SystemTime>,
+}
+
+impl Bindings {
+    pub fn new() -> Bindings {
+        Bindings {
+            bindings: vec![],
+            deadzone: default_deadzone(),
+            deadzones: HashMap::new(),
+            path: None,
+            modified: None,
+        }
+    }
+
+    pub fn load(path: PathBuf) -> GameResult<Bindings> {
+        let file = File::open(&path)?;
+        let raw: BindingsFile = serde_json::from_reader(file).map_err(|e| {
+            GameError::ResourceLoadError(format!("{}: {}", path.display(), e))
+        })?;
+        let mut bindings = Bindings::parse(raw)?;
+        bindings.modified = modified(&path);
+        bindings.path = Some(path);
+        Ok(bindings)
+    }
+
+    fn parse(raw: BindingsFile) -> GameResult<Bindings> {
+        let mut bindings = Bindings::new();
+        bindings.deadzone = raw.deadzone;
+
+        for (name, deadzone) in raw.deadzones {
+            bindings.deadzones.insert(axis(&name)?, deadzone);
+        }
+
+        for (action, inputs) in raw.actions {
+            for name in inputs.keys {
+                let key = Keycode::from_name(&name).ok_or_else(|| unknown("key", &name))?;
+                bindings.bind(action, Binding::Key(key));
+            }
+            for name in inputs.buttons {
+                let button = Button::from_string(&name).ok_or_else(|| unknown("button", &name))?;
+                bindings.bind(action, Binding::Button(button));
+            }
+            for a in inputs.axes {
+                let binding = Binding::Axis {
+                    axis: axis(&a.axis)?,
+                    min: a.min,
+                    max: a.max,
+                };
+                bindings.bind(action, binding);
+            }
+        }
+
+        Ok(bindings)
+    }
+
+    /// Adds another input for the action, taking it away from whatever it did before.
+    /// Inputs the action already has keep working.
+    pub fn bind(&mut self, action: Action, binding: Binding) {
+        self.unbind(binding);
+        self.bindings.push((binding, action));
+    }
+
+    /// Frees the input from whatever actions it was bound to.
+    pub fn unbind(&mut self, binding: Binding) {
+        self.bindings.retain(|&(b, _)| b != binding);
+    }
+
+    pub fn key(&self, key: Keycode) -> Vec<Action> {
+        self.find(|b| b == Binding::Key(key))
+    }
+
+    pub fn button(&self, button: Button) -> Vec<Action> {
+        self.find(|b| b == Binding::Button(button))
+    }
+
+    /// Every action bound to a range of the axis, paired with how far past the deadzone
+    /// the value holds it, from 0.0 for released to 1.0 for all the way out.
+    pub fn axis(&self, axis: Axis, value: i16) -> Vec<(Action, f64)> {
+        let deadzone = self.deadzones.get(&axis).cloned().unwrap_or(self.deadzone) as f64;
+        let amount = ((value as f64).abs() - deadzone) / (::std::i16::MAX as f64 - deadzone);
+        let amount = amount.max(0.0).min(1.0);
+
+        self.bindings
+            .iter()
+            .filter_map(|&(b, action)| match b {
+                Binding::Axis { axis: a, min, max } if a == axis => {
+                    let held = min <= value && value <= max;
+                    Some((action, if held { amount } else { 0.0 }))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Picks up the file again if it was saved since the last look. A broken file is
+    /// reported and the current bindings are kept.
+    pub fn poll(&mut self) {
+        let path = match self.path.clone() {
+            Some(path) => path,
+            None => return,
+        };
+        let modified = modified(&path);
+        if modified == self.modified {
+            return;
+        }
+        self.modified = modified;
+
+        match Bindings::load(path) {
+            Ok(bindings) => {
+                println!("Reloaded bindings");
+                *self = bindings;
+            }
+            Err(e) => println!("Keeping old bindings, {:?}", e),
+        }
+    }
+
+    fn find<F: Fn(Binding) -> bool>(&self, matches: F) -> Vec<Action> {
+        self.bindings
+            .iter()
+            .filter(|&&(b, _)| matches(b))
+            .map(|&(_, action)| action)
+            .collect()
+    }
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn axis(name: &str) -> GameResult<Axis> {
+    Axis::from_string(name).ok_or_else(|| unknown("axis", name))
+}
+
+fn unknown(what: &str, name: &str) -> GameError {
+    GameError::ResourceLoadError(format!("Unknown {} in bindings: {}", what, name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use components::InputState;
+
+    fn bindings(json: &str) -> GameResult<Bindings> {
+        Bindings::parse(serde_json::from_str(json).unwrap())
+    }
+
+    #[test]
+    fn reads_keys_buttons_and_axes() {
+        let b = bindings(
+            r#"{
+                "deadzones": { "leftx": 10000 },
+                "actions": {
+                    "left": {
+                        "keys": ["Left", "A"],
+                        "axes": [{ "axis": "leftx", "min": -32768, "max": 0 }]
+                    },
+                    "jump": { "keys": ["Space"], "buttons": ["a"] }
+                }
+            }"#,
+        ).unwrap();
+
+        assert_eq!(b.key(Keycode::Left), vec![Action::Left]);
+        assert_eq!(b.key(Keycode::A), vec![Action::Left]);
+        assert_eq!(b.button(Button::A), vec![Action::Jump]);
+        assert!(b.key(Keycode::Right).is_empty());
+
+        assert_eq!(b.axis(Axis::LeftX, -8000), vec![(Action::Left, 0.0)]);
+        assert!(b.axis(Axis::LeftX, -12000)[0].1 > 0.0);
+        assert_eq!(b.axis(Axis::LeftX, -32768), vec![(Action::Left, 1.0)]);
+        assert_eq!(b.axis(Axis::LeftX, 12000), vec![(Action::Left, 0.0)]);
+        assert!(b.axis(Axis::LeftY, -12000).is_empty());
+    }
+
+    #[test]
+    fn rebinds_at_runtime() {
+        let mut b = bindings(r#"{ "actions": { "attack": { "keys": ["Left Shift"] } } }"#)
+            .unwrap();
+        b.bind(Action::Attack, Binding::Key(Keycode::Z));
+        assert_eq!(b.key(Keycode::LShift), vec![Action::Attack]);
+        assert_eq!(b.key(Keycode::Z), vec![Action::Attack]);
+
+        b.bind(Action::Slide, Binding::Key(Keycode::Z));
+        assert_eq!(b.key(Keycode::Z), vec![Action::Slide]);
+
+        b.unbind(Binding::Key(Keycode::LShift));
+        assert!(b.key(Keycode::LShift).is_empty());
+    }
+
+    #[test]
+    fn centred_stick_keeps_the_dpad_held() {
+        let b = bindings(
+            r#"{
+                "actions": {
+                    "left": {
+                        "buttons": ["dpleft"],
+                        "axes": [{ "axis": "leftx", "min": -32768, "max": 0 }]
+                    }
+                }
+            }"#,
+        ).unwrap();
+        let mut input = InputState::new();
+
+        for action in b.button(Button::DPadLeft) {
+            input.set(action, true, 0.0);
+        }
+        for &value in [0, 300, -200, 12000].iter() {
+            for (action, amount) in b.axis(Axis::LeftX, value) {
+                input.set_axis(action, amount, 0.0);
+            }
+            assert!(input.left, "stick at {} let go of the d-pad", value);
+            assert_eq!(input.walk_scale, 1.0);
+        }
+
+        // the stick holds it on its own once the d-pad is let go
+        for (action, amount) in b.axis(Axis::LeftX, -32768) {
+            input.set_axis(action, amount, 0.0);
+        }
+        for action in b.button(Button::DPadLeft) {
+            input.set(action, false, 0.0);
+        }
+        assert!(input.left);
+        for (action, amount) in b.axis(Axis::LeftX, 0) {
+            input.set_axis(action, amount, 0.0);
+        }
+        assert!(!input.left);
+    }
+
+    #[test]
+    fn rejects_unknown_names() {
+        assert!(bindings(r#"{ "actions": { "jump": { "keys": ["Nope"] } } }"#).is_err());
+    }
+}
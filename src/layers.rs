@@ -0,0 +1,41 @@
+use resources::{CollisionLayers, Response};
+
+pub const PLAYERS: usize = 0;
+pub const ENEMIES: usize = 1;
+pub const PROJECTILES: usize = 2;
+pub const PICKUPS: usize = 3;
+pub const TRIGGERS: usize = 4;
+
+/// Actors bump into each other, everything else only reports who it touched.
+pub fn collision_layers() -> CollisionLayers {
+    let mut layers = CollisionLayers::new(5);
+    layers.set(PLAYERS, PLAYERS, Response::Resolve);
+    layers.set(PLAYERS, ENEMIES, Response::Resolve);
+    layers.set(ENEMIES, ENEMIES, Response::Resolve);
+
+    for &actor in &[PLAYERS, ENEMIES] {
+        layers.set(actor, PROJECTILES, Response::Report);
+        layers.set(actor, TRIGGERS, Response::Report);
+    }
+    layers.set(PLAYERS, PICKUPS, Response::Report);
+    layers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn responses_go_both_ways() {
+        let mut layers = collision_layers();
+        assert_eq!(layers.response(ENEMIES, PLAYERS), Response::Resolve);
+        assert_eq!(layers.response(PICKUPS, PLAYERS), Response::Report);
+        assert_eq!(layers.response(PICKUPS, ENEMIES), Response::Ignore);
+
+        let ghosts = layers.add_layer();
+        assert_eq!(layers.response(ghosts, PLAYERS), Response::Ignore);
+        layers.set(PLAYERS, ghosts, Response::Report);
+        assert_eq!(layers.response(ghosts, PLAYERS), Response::Report);
+        assert_eq!(layers.response(ghosts, 42), Response::Ignore);
+    }
+}
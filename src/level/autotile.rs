@@ -0,0 +1,161 @@
+use super::map;
+use marker::{Horizontal, Square};
+
+pub const N: u8 = 1;
+pub const NE: u8 = 2;
+pub const E: u8 = 4;
+pub const SE: u8 = 8;
+pub const S: u8 = 16;
+pub const SW: u8 = 32;
+pub const W: u8 = 64;
+pub const NW: u8 = 128;
+
+struct Rule {
+    solid: u8,
+    empty: u8,
+    square: Square,
+}
+
+// First matching rule wins, the last one matches everything.
+static GROUND_RULES: [Rule; 13] = [
+    Rule { solid: 0, empty: N | W, square: Square::LT },
+    Rule { solid: 0, empty: N | E, square: Square::RT },
+    Rule { solid: NW, empty: N, square: Square::ILT },
+    Rule { solid: NE, empty: N, square: Square::IRT },
+    Rule { solid: 0, empty: N, square: Square::MT },
+    Rule { solid: 0, empty: S | W, square: Square::LB },
+    Rule { solid: 0, empty: S | E, square: Square::RB },
+    Rule { solid: 0, empty: S, square: Square::MB },
+    Rule { solid: 0, empty: W, square: Square::LM },
+    Rule { solid: 0, empty: E, square: Square::RM },
+    Rule { solid: 0, empty: NW, square: Square::IBR },
+    Rule { solid: 0, empty: NE, square: Square::IBL },
+    Rule { solid: 0, empty: 0, square: Square::MM },
+];
+
+pub struct AutoTiler;
+
+impl AutoTiler {
+    pub fn ground(neighbours: u8) -> Square {
+        GROUND_RULES
+            .iter()
+            .find(|r| neighbours & r.solid == r.solid && !neighbours & r.empty == r.empty)
+            .map(|r| r.square.clone())
+            .unwrap_or(Square::MM)
+    }
+
+    /// A platform ends next to anything but more platform, or a wall it runs into.
+    pub fn platform(left: usize, right: usize) -> Horizontal {
+        let ends = |code: usize| code != map::ONE_WAY && code != map::BLOCK;
+        if ends(left) {
+            Horizontal::Left
+        } else if ends(right) {
+            Horizontal::Right
+        } else {
+            Horizontal::Center
+        }
+    }
+
+    pub fn neighbours(t: &Vec<Vec<usize>>, h: usize, v: usize) -> u8 {
+        let solid = |dh: isize, dv: isize| {
+            let (h, v) = (h as isize + dh, v as isize + dv);
+            if h < 0 || v < 0 || v as usize >= t.len() || h as usize >= t[v as usize].len() {
+                true
+            } else {
+                t[v as usize][h as usize] == map::BLOCK
+            }
+        };
+
+        let mut mask = 0;
+        for &(dh, dv, bit) in [
+            (0, -1, N),
+            (1, -1, NE),
+            (1, 0, E),
+            (1, 1, SE),
+            (0, 1, S),
+            (-1, 1, SW),
+            (-1, 0, W),
+            (-1, -1, NW),
+        ].iter()
+        {
+            if solid(dh, dv) {
+                mask |= bit;
+            }
+        }
+        mask
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn has(mask: u8, bit: u8) -> bool {
+        mask & bit != 0
+    }
+
+    #[test]
+    fn every_neighbourhood_gets_a_consistent_tile() {
+        for mask in 0..256 {
+            let mask = mask as u8;
+            let sqr = AutoTiler::ground(mask);
+            let top = [Square::LT, Square::MT, Square::RT, Square::ILT, Square::IRT];
+            let bottom = [Square::LB, Square::MB, Square::RB];
+
+            assert_eq!(top.contains(&sqr), !has(mask, N), "{:08b} -> {:?}", mask, sqr);
+            if has(mask, N) {
+                assert_eq!(bottom.contains(&sqr), !has(mask, S), "{:08b} -> {:?}", mask, sqr);
+            }
+
+            let expected = match sqr {
+                Square::LT => !has(mask, W),
+                Square::RT => has(mask, W) && !has(mask, E),
+                Square::ILT => has(mask, W) && has(mask, E) && has(mask, NW),
+                Square::IRT => has(mask, W) && has(mask, E) && !has(mask, NW) && has(mask, NE),
+                Square::MT => has(mask, W) && has(mask, E) && !has(mask, NW) && !has(mask, NE),
+                Square::LB => !has(mask, W),
+                Square::RB => has(mask, W) && !has(mask, E),
+                Square::MB => has(mask, W) && has(mask, E),
+                Square::LM => !has(mask, W),
+                Square::RM => has(mask, W) && !has(mask, E),
+                Square::IBR => has(mask, W) && has(mask, E) && !has(mask, NW),
+                Square::IBL => has(mask, W) && has(mask, E) && has(mask, NW) && !has(mask, NE),
+                Square::MM => mask & (N | NE | E | S | W | NW) == N | NE | E | S | W | NW,
+            };
+            assert!(expected, "{:08b} -> {:?}", mask, sqr);
+        }
+    }
+
+    #[test]
+    fn diagonals_below_do_not_matter() {
+        for mask in 0..256 {
+            let mask = mask as u8;
+            assert_eq!(AutoTiler::ground(mask), AutoTiler::ground(mask | SE | SW));
+        }
+    }
+
+    #[test]
+    fn border_is_solid() {
+        let t = vec![vec![1, 1, 1, 1, 1], vec![1, 0, 0, 0, 1], vec![1, 1, 1, 1, 1]];
+        assert_eq!(AutoTiler::neighbours(&t, 0, 0), !SE);
+        assert_eq!(AutoTiler::ground(AutoTiler::neighbours(&t, 2, 2)), Square::MT);
+        assert_eq!(AutoTiler::ground(AutoTiler::neighbours(&t, 1, 2)), Square::ILT);
+        assert_eq!(AutoTiler::ground(AutoTiler::neighbours(&t, 0, 1)), Square::RM);
+    }
+
+    #[test]
+    fn platform_ends() {
+        assert_eq!(AutoTiler::platform(map::EMPTY, map::ONE_WAY), Horizontal::Left);
+        assert_eq!(AutoTiler::platform(map::ONE_WAY, map::EMPTY), Horizontal::Right);
+        assert_eq!(AutoTiler::platform(map::BLOCK, map::ONE_WAY), Horizontal::Center);
+        assert_eq!(AutoTiler::platform(map::EMPTY, map::EMPTY), Horizontal::Left);
+
+        for &code in [map::LADDER, map::WATER, map::SPIKES, map::SLOPE_UP, map::SLOPE_DOWN_LOW]
+            .iter()
+        {
+            assert_eq!(AutoTiler::platform(code, map::ONE_WAY), Horizontal::Left, "{}", code);
+            assert_eq!(AutoTiler::platform(map::ONE_WAY, code), Horizontal::Right, "{}", code);
+        }
+        assert_eq!(AutoTiler::platform(map::ONE_WAY, map::BLOCK), Horizontal::Center);
+    }
+}
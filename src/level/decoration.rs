@@ -0,0 +1,226 @@
+use rand::{Rng, SeedableRng, XorShiftRng};
+
+use super::index::{LevelAssetIndex, tile_hash};
+use super::map;
+use marker::geom::{Point, Rect};
+
+#[derive(Debug, Clone)]
+pub struct DecorationSettings {
+    pub density: f64,
+}
+
+impl Default for DecorationSettings {
+    fn default() -> DecorationSettings {
+        DecorationSettings { density: 0.2 }
+    }
+}
+
+pub struct Decorations {
+    pub ground: Vec<(Rect, Point)>,
+    pub surface: Vec<(Rect, Point)>,
+}
+
+pub struct Decorator;
+
+impl Decorator {
+    pub fn decorate(
+        terrain_data: &Vec<Vec<usize>>,
+        index: &LevelAssetIndex,
+        settings: &DecorationSettings,
+        seed: u32,
+        tile_size: f32,
+    ) -> Decorations {
+        let height = terrain_data.len();
+        let width = terrain_data[0].len();
+        let pixel_height = height as f32 * tile_size;
+
+        let tile = |h: isize, v: isize| if h < 0 || v < 0 || h as usize >= width ||
+            v as usize >= height
+        {
+            map::BLOCK
+        } else {
+            terrain_data[v as usize][h as usize]
+        };
+        let is_surface = |h: isize, v: isize| {
+            (tile(h, v) == map::BLOCK || tile(h, v) == map::ONE_WAY) &&
+                tile(h, v - 1) == map::EMPTY
+        };
+
+        let mut ground = vec![];
+        let mut surface = vec![];
+
+        for v in 0..height {
+            let mut occupied: Vec<(f32, f32)> = vec![];
+
+            for h in 0..width {
+                let (hi, vi) = (h as isize, v as isize);
+                let mut rng = tile_rng(seed, h, v);
+                let x = h as f32 * tile_size;
+                let y = v as f32 * tile_size - pixel_height;
+
+                if tile(hi, vi) == map::BLOCK && tile(hi, vi - 1) == map::BLOCK &&
+                    rng.next_f64() < settings.density
+                {
+                    if let Some((rect, _)) = index.find_object(false, &mut rng) {
+                        ground.push((rect, Point { x, y }));
+                    }
+                }
+
+                if is_surface(hi, vi) && rng.next_f64() < settings.density {
+                    if let Some((rect, size)) = index.find_object(true, &mut rng) {
+                        let slack = tile_size - size.w;
+                        let left = x + if slack > 0.0 {
+                            rng.gen_range(0.0, slack)
+                        } else {
+                            slack / 2.0
+                        };
+                        let right = left + size.w;
+
+                        let first = (left / tile_size).floor() as isize;
+                        let last = ((right - 1.0) / tile_size).floor() as isize;
+                        let supported = (first..last + 1).all(|c| is_surface(c, vi));
+                        let free = occupied.iter().all(|&(l, r)| right <= l || left >= r);
+
+                        if supported && free {
+                            occupied.push((left, right));
+                            surface.push((
+                                rect,
+                                Point {
+                                    x: left,
+                                    y: y - size.h,
+                                },
+                            ));
+                        }
+                    }
+                }
+            }
+        }
+
+        Decorations { ground, surface }
+    }
+}
+
+fn tile_rng(seed: u32, h: usize, v: usize) -> XorShiftRng {
+    let hash = tile_hash(seed ^ 0xDEC0_u32, h, v);
+    XorShiftRng::from_seed([hash as u32, (hash >> 32) as u32, 0x7F4A_7C15, 0xF39C_C060])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use marker::{SpriteData, SpriteType};
+
+    const TILE: f32 = 128.0;
+
+    fn sprite(name: &str, w: f32, h: f32) -> SpriteData {
+        SpriteData {
+            // drawn at the size of the frame, so the rect tells how big a decoration is
+            on_screen_frame: Rect::new(0.0, 0.0, w, h),
+            frame: Rect::new(0.0, 0.0, w, h),
+            markers: SpriteType::Object,
+            name: format!("{}.png", name),
+            index: 0,
+            hitboxes: vec![],
+        }
+    }
+
+    fn index() -> LevelAssetIndex {
+        let ground = vec![sprite("Bone", 60.0, 40.0)];
+        let objects = vec![
+            sprite("Bush", 60.0, 50.0),
+            sprite("Crate", 100.0, 100.0),
+            sprite("Tree", 200.0, 250.0),
+        ];
+        LevelAssetIndex::from_sprites(&ground, &objects, 1)
+    }
+
+    // a floor two blocks thick with a three tile one-way platform above it
+    fn terrain() -> Vec<Vec<usize>> {
+        let mut t = vec![vec![map::BLOCK; 12]; 7];
+        for v in 1..5 {
+            for h in 1..11 {
+                t[v][h] = map::EMPTY;
+            }
+        }
+        for h in 4..7 {
+            t[2][h] = map::ONE_WAY;
+        }
+        t
+    }
+
+    fn decorate(density: f64, seed: u32) -> Decorations {
+        let settings = DecorationSettings { density };
+        Decorator::decorate(&terrain(), &index(), &settings, seed, TILE)
+    }
+
+    fn tile_at(t: &Vec<Vec<usize>>, x: f32, y: f32) -> (usize, usize) {
+        let pixel_height = t.len() as f32 * TILE;
+        ((x / TILE).floor() as usize, ((y + pixel_height) / TILE).floor() as usize)
+    }
+
+    #[test]
+    fn nothing_floats() {
+        let t = terrain();
+        for seed in 0..20 {
+            let d = decorate(1.0, seed);
+            assert!(!d.surface.is_empty());
+
+            for &(ref rect, ref at) in d.surface.iter() {
+                let (_, v) = tile_at(&t, at.x, at.y + rect.h);
+                let first = (at.x / TILE).floor() as usize;
+                let last = ((at.x + rect.w - 1.0) / TILE).floor() as usize;
+                for h in first..last + 1 {
+                    assert!(t[v][h] == map::BLOCK || t[v][h] == map::ONE_WAY, "{:?} floats", at);
+                    assert_eq!(t[v - 1][h], map::EMPTY, "{:?} is buried", at);
+                }
+            }
+            for &(_, ref at) in d.ground.iter() {
+                let (h, v) = tile_at(&t, at.x, at.y);
+                assert_eq!(t[v][h], map::BLOCK);
+                assert!(v == 0 || t[v - 1][h] == map::BLOCK, "{:?} sticks out", at);
+            }
+        }
+    }
+
+    #[test]
+    fn nothing_overlaps() {
+        for seed in 0..20 {
+            let d = decorate(1.0, seed);
+            for (i, &(ref ra, ref a)) in d.surface.iter().enumerate() {
+                for &(ref rb, ref b) in d.surface[i + 1..].iter() {
+                    if a.y + ra.h != b.y + rb.h {
+                        continue;
+                    }
+                    assert!(
+                        a.x + ra.w <= b.x || b.x + rb.w <= a.x,
+                        "{:?} and {:?} overlap",
+                        a,
+                        b
+                    );
+                }
+            }
+            for (i, &(_, ref a)) in d.ground.iter().enumerate() {
+                assert!(d.ground[i + 1..].iter().all(|&(_, ref b)| a != b));
+            }
+        }
+    }
+
+    #[test]
+    fn zero_density_places_nothing() {
+        for seed in 0..20 {
+            let d = decorate(0.0, seed);
+            assert!(d.ground.is_empty());
+            assert!(d.surface.is_empty());
+        }
+    }
+
+    #[test]
+    fn same_seed_same_layout() {
+        let (a, b) = (decorate(0.5, 3), decorate(0.5, 3));
+        assert_eq!(a.ground, b.ground);
+        assert_eq!(a.surface, b.surface);
+
+        let c = decorate(0.5, 4);
+        assert!(a.ground != c.ground || a.surface != c.surface);
+    }
+}
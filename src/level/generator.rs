@@ -0,0 +1,325 @@
+use rand::{Rng, SeedableRng, XorShiftRng};
+use std::collections::VecDeque;
+
+use super::map;
+use components::MovementProfile;
+
+const ATTEMPTS: usize = 32;
+
+pub struct GeneratedLevel {
+    pub terrain_data: Vec<Vec<usize>>,
+    pub spawn: (usize, usize),
+    pub exit: (usize, usize),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Room {
+    x: usize,
+    y: usize,
+    w: usize,
+    h: usize,
+}
+
+impl Room {
+    fn right(&self) -> usize {
+        self.x + self.w
+    }
+
+    fn floor(&self) -> usize {
+        self.y + self.h
+    }
+}
+
+pub struct Generator;
+
+impl Generator {
+    pub const MIN_WIDTH: usize = 16;
+    pub const MIN_HEIGHT: usize = 10;
+
+    /// A level `profile` can get through from spawn to exit, `None` when no attempt
+    /// turned out completable.
+    pub fn generate(
+        seed: u32,
+        width: usize,
+        height: usize,
+        tile_size: f64,
+        profile: &MovementProfile,
+    ) -> Option<GeneratedLevel> {
+        let width = width.max(Generator::MIN_WIDTH);
+        let height = height.max(Generator::MIN_HEIGHT);
+        let reach = Reachability::new(tile_size, profile);
+        let mut rng = XorShiftRng::from_seed([seed, 0x6C07_8965, 0x4F1B_BCDC, 0x2545_F491]);
+
+        for _ in 0..ATTEMPTS {
+            let level = Generator::attempt(&mut rng, width, height);
+            if reach.is_completable(&level.terrain_data, level.spawn, level.exit) {
+                return Some(level);
+            }
+        }
+
+        None
+    }
+
+    fn attempt<R: Rng>(rng: &mut R, width: usize, height: usize) -> GeneratedLevel {
+        let mut t = vec![vec![map::BLOCK; width]; height];
+
+        let count = ((width - 2) / 12).max(1);
+        let segment = (width - 2) / count;
+        let max_h = 8.min(height - 4);
+
+        let mut rooms = vec![];
+        for i in 0..count {
+            let w = rng.gen_range(6, segment);
+            let h = rng.gen_range(4, max_h + 1);
+            let x = 1 + i * segment + rng.gen_range(0, segment - w);
+            let y = rng.gen_range(1, height - 1 - h);
+            let room = Room { x, y, w, h };
+            carve(&mut t, room.x, room.y, room.w, room.h);
+            rooms.push(room);
+        }
+
+        for pair in rooms.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let low = a.floor().max(b.floor());
+
+            carve(&mut t, a.right(), low - 3, b.x - a.right(), 3);
+
+            if a.floor() < low {
+                Generator::ladder(&mut t, a.right() - 3, a.floor(), low);
+            } else if b.floor() < low {
+                Generator::ladder(&mut t, b.x, b.floor(), low);
+            }
+        }
+
+        let (first, last) = (rooms[0], rooms[rooms.len() - 1]);
+        GeneratedLevel {
+            terrain_data: t,
+            spawn: (first.x + 1, first.floor() - 1),
+            exit: (last.right() - 2, last.floor() - 1),
+        }
+    }
+
+    // Three tiles wide shaft from `top` floor down to `bottom` floor, with
+    // staggered one-way platforms no more than a jump apart.
+    fn ladder(t: &mut Vec<Vec<usize>>, x: usize, top: usize, bottom: usize) {
+        carve(t, x, top, 3, bottom - top);
+
+        let mut p = bottom;
+        let mut left = true;
+        while p > top + 3 {
+            p -= 3;
+            let from = if left { x } else { x + 1 };
+            t[p][from] = map::ONE_WAY;
+            t[p][from + 1] = map::ONE_WAY;
+            left = !left;
+        }
+    }
+
+}
+
+fn carve(t: &mut Vec<Vec<usize>>, x: usize, y: usize, w: usize, h: usize) {
+    for v in y..y + h {
+        for c in x..x + w {
+            t[v][c] = map::EMPTY;
+        }
+    }
+}
+
+// Headless check of where a character can get to, in tile units. A standing
+// spot is a tile with room for the body above it and ground below it.
+pub struct Reachability {
+    rise: isize,
+    reach: Vec<usize>,
+}
+
+impl Reachability {
+    pub fn new(tile_size: f64, profile: &MovementProfile) -> Reachability {
+        let g = -profile.gravity;
+        let v = profile.jump_speed;
+        let apex = v * v / (2.0 * g);
+        let rise = (apex / tile_size).floor() as isize;
+
+        // horizontal reach for every climb from `rise` down to a fall of 64 tiles,
+        // assuming only half of the walking speed to stay on the safe side
+        let reach = (0..rise + 65)
+            .map(|i| {
+                let climb = (rise - i) as f64 * tile_size;
+                let air_time = v / g + (2.0 * (apex - climb) / g).sqrt();
+                let dx = profile.walk_speed / 2.0 * air_time;
+                ((dx / tile_size).floor() as usize).max(2) - 1
+            })
+            .collect();
+
+        Reachability { rise, reach }
+    }
+
+    pub fn is_completable(&self, t: &Vec<Vec<usize>>, from: (usize, usize), to: (usize, usize)) -> bool {
+        self.reachable(t, from)[to.1][to.0]
+    }
+
+    pub fn reachable(&self, t: &Vec<Vec<usize>>, from: (usize, usize)) -> Vec<Vec<bool>> {
+        let height = t.len();
+        let width = t[0].len();
+        let mut visited = vec![vec![false; width]; height];
+        let mut queue = VecDeque::new();
+
+        if standable(t, from.0 as isize, from.1 as isize) {
+            visited[from.1][from.0] = true;
+            queue.push_back(from);
+        }
+
+        while let Some((h, v)) = queue.pop_front() {
+            let (h, v) = (h as isize, v as isize);
+            let mut next = vec![];
+
+            for &dir in [-1, 1].iter() {
+                let nh = h + dir;
+                if body_fits(t, nh, v) {
+                    let mut fv = v;
+                    while !standable(t, nh, fv) && body_fits(t, nh, fv + 1) {
+                        fv += 1;
+                    }
+                    next.push((nh, fv));
+                }
+            }
+
+            for (i, reach) in self.reach.iter().enumerate() {
+                let tv = v - (self.rise - i as isize);
+                if tv < 1 || tv >= height as isize {
+                    continue;
+                }
+                for dx in 1..*reach as isize + 1 {
+                    for &dir in [-1, 1].iter() {
+                        let th = h + dir * dx;
+                        if standable(t, th, tv) && arc_clear(t, h, v, th, tv) {
+                            next.push((th, tv));
+                        }
+                    }
+                }
+            }
+
+            for (nh, nv) in next {
+                if standable(t, nh, nv) && !visited[nv as usize][nh as usize] {
+                    visited[nv as usize][nh as usize] = true;
+                    queue.push_back((nh as usize, nv as usize));
+                }
+            }
+        }
+
+        visited
+    }
+}
+
+fn tile(t: &Vec<Vec<usize>>, h: isize, v: isize) -> usize {
+    if h < 0 || v < 0 || v as usize >= t.len() || h as usize >= t[0].len() {
+        map::BLOCK
+    } else {
+        t[v as usize][h as usize]
+    }
+}
+
+fn body_fits(t: &Vec<Vec<usize>>, h: isize, v: isize) -> bool {
+    tile(t, h, v) != map::BLOCK && tile(t, h, v - 1) != map::BLOCK
+}
+
+fn standable(t: &Vec<Vec<usize>>, h: isize, v: isize) -> bool {
+    body_fits(t, h, v) && tile(t, h, v) == map::EMPTY && map::is_floor(tile(t, h, v + 1))
+}
+
+// Conservative jump path: straight up in the start column, across at the
+// height of the higher spot, then straight down into the target column.
+fn arc_clear(t: &Vec<Vec<usize>>, h0: isize, v0: isize, h1: isize, v1: isize) -> bool {
+    let top = v0.min(v1);
+    let up = (top..v0 + 1).all(|v| body_fits(t, h0, v));
+    let (l, r) = (h0.min(h1), h0.max(h1));
+    let across = (l..r + 1).all(|h| body_fits(t, h, top));
+    let down = (top..v1 + 1).all(|v| body_fits(t, h1, v));
+    up && across && down
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_levels_are_completable() {
+        let profile = MovementProfile::default();
+        let reach = Reachability::new(128.0, &profile);
+        for seed in 0..50 {
+            for &(w, h) in [(16, 10), (48, 20), (96, 30)].iter() {
+                let level = Generator::generate(seed, w, h, 128.0, &profile)
+                    .unwrap_or_else(|| panic!("seed {} gave up", seed));
+                let t = &level.terrain_data;
+                assert_eq!((t[0].len(), t.len()), (w, h));
+                assert_eq!(map::find_open_border(t), None);
+                assert!(reach.is_completable(t, level.spawn, level.exit), "seed {}", seed);
+            }
+        }
+    }
+
+    #[test]
+    fn generation_is_deterministic() {
+        let profile = MovementProfile::default();
+        let a = Generator::generate(9, 64, 24, 128.0, &profile).unwrap();
+        let b = Generator::generate(9, 64, 24, 128.0, &profile).unwrap();
+        assert_eq!(a.terrain_data, b.terrain_data);
+        assert_eq!((a.spawn, a.exit), (b.spawn, b.exit));
+    }
+
+    #[test]
+    fn walls_higher_than_a_jump_are_not_climbable() {
+        let t = ::level::TileMap::parse(
+            "test",
+            "##########\n\
+             #........#\n\
+             #....#...#\n\
+             #....#...#\n\
+             #....#...#\n\
+             #....#...#\n\
+             ##########\n",
+        ).ok()
+            .unwrap()
+            .terrain_data;
+        let reach = Reachability::new(128.0, &MovementProfile::default());
+        assert!(!reach.is_completable(&t, (1, 5), (7, 5)));
+
+        let t = ::level::TileMap::parse(
+            "test",
+            "##########\n\
+             #........#\n\
+             #........#\n\
+             #....#...#\n\
+             #....#...#\n\
+             ##########\n",
+        ).ok()
+            .unwrap()
+            .terrain_data;
+        assert!(reach.is_completable(&t, (1, 4), (7, 4)));
+    }
+
+    #[test]
+    fn higher_jumps_reach_higher_ledges() {
+        let t = ::level::TileMap::parse(
+            "test",
+            "##########\n\
+             #........#\n\
+             #........#\n\
+             #........#\n\
+             #....#####\n\
+             #....#####\n\
+             #....#####\n\
+             #....#####\n\
+             ##########\n",
+        ).ok()
+            .unwrap()
+            .terrain_data;
+        let default = MovementProfile::default();
+        assert!(!Reachability::new(128.0, &default).is_completable(&t, (1, 7), (6, 3)));
+
+        let floaty = MovementProfile {
+            jump_speed: 2000.0,
+            ..MovementProfile::default()
+        };
+        assert!(Reachability::new(128.0, &floaty).is_completable(&t, (1, 7), (6, 3)));
+    }
+}
@@ -2,11 +2,11 @@ use std::collections::HashMap;
 
 use super::LoadedAssets;
 
-use self::geom::Rect;
+use self::geom::{Rect, Size};
 
 use marker::*;
 
-use rand;
+use rand::Rng;
 
 #[derive(Debug)]
 struct GroundIndex {
@@ -18,12 +18,14 @@ struct PlatformIndex {
 }
 #[derive(Debug)]
 struct ObjectIndex {
-    ground: Vec<Rect>,
-    surface: Vec<Rect>,
+    ground: Vec<(Rect, Size)>,
+    surface: Vec<(Rect, Size)>,
+    named: HashMap<String, (Rect, Size)>,
 }
 
 #[derive(Debug)]
 pub struct LevelAssetIndex {
+    seed: u32,
     ground: GroundIndex,
     platforms: PlatformIndex,
     objects: ObjectIndex,
@@ -31,13 +33,18 @@ pub struct LevelAssetIndex {
 
 #[allow(dead_code)]
 impl LevelAssetIndex {
-    pub fn build(loaded: &LoadedAssets) -> LevelAssetIndex {
+    pub fn build(loaded: &LoadedAssets, seed: u32) -> LevelAssetIndex {
+        LevelAssetIndex::from_sprites(&loaded.ground.data, &loaded.objects.data, seed)
+    }
+
+    pub fn from_sprites(ground: &[SpriteData], objects: &[SpriteData], seed: u32) -> LevelAssetIndex {
         let mut ground_sqr: HashMap<Square, Vec<Rect>> = HashMap::new();
         let mut platform_hor: HashMap<Horizontal, Vec<Rect>> = HashMap::with_capacity(3);
         let mut ground_obj = vec![];
         let mut surface_obj = vec![];
+        let mut named_obj = HashMap::new();
 
-        for gd in loaded.ground.data.iter() {
+        for gd in ground.iter() {
             match &gd.markers {
                 &SpriteType::Ground { square: ref sqr } => for s in sqr.iter() {
                     let mut p = true;
@@ -59,22 +66,30 @@ impl LevelAssetIndex {
                         .entry(h.clone())
                         .or_insert({ vec![gd.on_screen_frame.clone()] });
                 },
-                &SpriteType::Object => ground_obj.push(gd.on_screen_frame.clone()),
+                &SpriteType::Object => ground_obj.push((gd.on_screen_frame.clone(), size_of(gd))),
             }
         }
 
-        for od in loaded.objects.data.iter() {
+        for od in objects.iter() {
             match od.markers {
-                SpriteType::Object => surface_obj.push(od.on_screen_frame.clone()),
+                SpriteType::Object => {
+                    surface_obj.push((od.on_screen_frame.clone(), size_of(od)));
+                    named_obj.insert(
+                        od.name.trim_right_matches(".png").to_owned(),
+                        (od.on_screen_frame.clone(), size_of(od)),
+                    );
+                }
                 _ => (),
             }
         }
 
         let index = LevelAssetIndex {
+            seed,
             ground: GroundIndex { square: ground_sqr },
             objects: ObjectIndex {
                 ground: ground_obj,
                 surface: surface_obj,
+                named: named_obj,
             },
             platforms: PlatformIndex {
                 horizontal: platform_hor,
@@ -84,28 +99,98 @@ impl LevelAssetIndex {
         index
     }
 
-    pub fn find_ground(&self, sqr: Square) -> Option<Rect> {
-        self.ground.square.get(&sqr).and_then(random_from)
+    pub fn find_ground(&self, sqr: Square, h: usize, v: usize) -> Option<Rect> {
+        let seed = self.seed;
+        self.ground.square.get(&sqr).and_then(|r| variant_of(r, seed, h, v))
     }
-    pub fn find_platform(&self, hor: Horizontal) -> Option<Rect> {
-        self.platforms.horizontal.get(&hor).and_then(random_from)
+    pub fn find_platform(&self, hor: Horizontal, h: usize, v: usize) -> Option<Rect> {
+        let seed = self.seed;
+        self.platforms.horizontal.get(&hor).and_then(|r| variant_of(r, seed, h, v))
     }
 
-    pub fn find_object(&self, surface: bool) -> Option<Rect> {
+    pub fn find_object<R: Rng>(&self, surface: bool, rng: &mut R) -> Option<(Rect, Size)> {
         let r = if surface {
             &self.objects.surface
         } else {
             &self.objects.ground
         };
-        random_from(&r)
+        rng.choose(r).cloned()
+    }
+
+    pub fn find_object_named(&self, name: &str) -> Option<(Rect, Size)> {
+        self.objects.named.get(name.trim_right_matches(".png")).cloned()
+    }
+}
+
+fn size_of(sd: &SpriteData) -> Size {
+    Size {
+        w: sd.frame.w,
+        h: sd.frame.h,
     }
 }
 
-fn random_from<T: Clone>(from: &Vec<T>) -> Option<T> {
+pub fn tile_hash(seed: u32, h: usize, v: usize) -> u64 {
+    let mut x = (seed as u64) << 32 ^ (h as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) ^
+        (v as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+fn variant_of<T: Clone>(from: &Vec<T>, seed: u32, h: usize, v: usize) -> Option<T> {
     if from.len() > 0 {
-        let ix = rand::random::<usize>() % from.len();
+        let ix = (tile_hash(seed, h, v) % from.len() as u64) as usize;
         Some(from[ix].clone())
     } else {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ground_tile(ix: usize) -> SpriteData {
+        SpriteData {
+            on_screen_frame: Rect::new(ix as f32 * 0.25, 0.0, 0.25, 1.0),
+            frame: Rect::new(ix as f32 * 128.0, 0.0, 128.0, 128.0),
+            markers: SpriteType::Ground { square: vec![Square::MM] },
+            name: format!("Tile ({}).png", ix),
+            index: ix,
+            hitboxes: vec![],
+        }
+    }
+
+    fn index(seed: u32) -> LevelAssetIndex {
+        let ground = (0..4).map(ground_tile).collect::<Vec<_>>();
+        LevelAssetIndex::from_sprites(&ground, &[], seed)
+    }
+
+    #[test]
+    fn variants_are_stable_per_tile() {
+        let a = index(7);
+        let b = index(7);
+        for h in 0..20 {
+            for v in 0..20 {
+                assert_eq!(a.find_ground(Square::MM, h, v), b.find_ground(Square::MM, h, v));
+            }
+        }
+    }
+
+    #[test]
+    fn variants_depend_on_seed_and_cover_all_sprites() {
+        let a = index(1);
+        let b = index(2);
+        let mut differs = false;
+        let mut seen = vec![false; 4];
+        for h in 0..20 {
+            for v in 0..20 {
+                let r = a.find_ground(Square::MM, h, v).unwrap();
+                differs |= Some(r.clone()) != b.find_ground(Square::MM, h, v);
+                seen[(r.x / 0.25) as usize] = true;
+            }
+        }
+        assert!(differs);
+        assert!(seen.iter().all(|s| *s));
+    }
+}
@@ -12,15 +12,10 @@ pub struct LoadedAssets {
 }
 
 impl LoadedAssets {
-    pub fn load_assets(ctx: &mut Context, tpe: LevelType) -> GameResult<LoadedAssets> {
-        let (g, o, bg) = match tpe {
-            LevelType::Graveyard => {
-                let g = Loader::load_sprite_sheet(ctx, "/level/graveyard/level_ground")?;
-                let o = Loader::load_sprite_sheet(ctx, "/level/graveyard/level_objects")?;
-                let bg = Image::new(ctx, "/level/graveyard/background.png")?;
-                (g, o, bg)
-            }
-        };
+    pub fn load_assets(ctx: &mut Context, theme: &str) -> GameResult<LoadedAssets> {
+        let g = Loader::load_sprite_sheet(ctx, &format!("/level/{}/level_ground", theme))?;
+        let o = Loader::load_sprite_sheet(ctx, &format!("/level/{}/level_objects", theme))?;
+        let bg = Image::new(ctx, format!("/level/{}/background.png", theme))?;
         Ok(LoadedAssets {
             ground: g,
             objects: o,
@@ -0,0 +1,245 @@
+use ggez::{Context, GameError, GameResult};
+use std::io::Read;
+
+use super::terrain::{Slope, TileType};
+
+pub const EMPTY: usize = 0;
+pub const BLOCK: usize = 1;
+pub const ONE_WAY: usize = 2;
+pub const LADDER: usize = 3;
+pub const SPIKES: usize = 4;
+pub const WATER: usize = 5;
+pub const SLOPE_UP: usize = 6;
+pub const SLOPE_DOWN: usize = 7;
+pub const SLOPE_UP_LOW: usize = 8;
+pub const SLOPE_UP_HIGH: usize = 9;
+pub const SLOPE_DOWN_HIGH: usize = 10;
+pub const SLOPE_DOWN_LOW: usize = 11;
+
+pub struct TileMap {
+    pub seed: u32,
+    pub terrain_data: Vec<Vec<usize>>,
+}
+
+impl TileMap {
+    pub fn load(ctx: &mut Context, path: &str) -> GameResult<TileMap> {
+        let mut file = ctx.filesystem.open(path)?;
+        let mut source = String::new();
+        file.read_to_string(&mut source)?;
+
+        TileMap::parse(path, &source)
+    }
+
+    pub fn parse(name: &str, source: &str) -> GameResult<TileMap> {
+        let mut terrain_data: Vec<Vec<usize>> = vec![];
+        let mut seed = 0;
+
+        for (line_ix, line) in source.lines().enumerate() {
+            let line = line.trim_right();
+            if line.is_empty() {
+                continue;
+            }
+
+            if terrain_data.is_empty() {
+                if let Some(colon) = line.find(':') {
+                    let (key, value) = (line[..colon].trim(), line[colon + 1..].trim());
+                    let value_col = line.len() - line[colon + 1..].trim_left().len();
+                    match key {
+                        "seed" => {
+                            seed = value.parse().map_err(|_| {
+                                error(name, line_ix, value_col, "seed must be an integer")
+                            })?
+                        }
+                        _ => {
+                            return Err(error(
+                                name,
+                                line_ix,
+                                0,
+                                &format!("unknown property '{}'", key),
+                            ))
+                        }
+                    }
+                    continue;
+                }
+            }
+
+            let mut row = Vec::with_capacity(line.len());
+            for (col_ix, c) in line.chars().enumerate() {
+                match tile_code(c) {
+                    Some(code) => row.push(code),
+                    None => {
+                        return Err(error(
+                            name,
+                            line_ix,
+                            col_ix,
+                            &format!("unknown tile code '{}'", c),
+                        ))
+                    }
+                }
+            }
+
+            if let Some(expected) = terrain_data.first().map(|r| r.len()) {
+                if row.len() != expected {
+                    return Err(error(
+                        name,
+                        line_ix,
+                        row.len().min(expected),
+                        &format!("row has {} tiles, expected {}", row.len(), expected),
+                    ));
+                }
+            }
+
+            terrain_data.push(row);
+        }
+
+        if terrain_data.len() < 3 || terrain_data[0].len() < 3 {
+            return Err(GameError::ResourceLoadError(format!(
+                "{}: level must be at least 3x3 tiles",
+                name
+            )));
+        }
+
+        check_border(name, source, &terrain_data)?;
+
+        Ok(TileMap { seed, terrain_data })
+    }
+}
+
+fn tile_code(c: char) -> Option<usize> {
+    match c {
+        '.' => Some(EMPTY),
+        '#' => Some(BLOCK),
+        '=' => Some(ONE_WAY),
+        'H' => Some(LADDER),
+        '^' => Some(SPIKES),
+        '~' => Some(WATER),
+        '/' => Some(SLOPE_UP),
+        '\\' => Some(SLOPE_DOWN),
+        'r' => Some(SLOPE_UP_LOW),
+        'R' => Some(SLOPE_UP_HIGH),
+        'L' => Some(SLOPE_DOWN_HIGH),
+        'l' => Some(SLOPE_DOWN_LOW),
+        _ => None,
+    }
+}
+
+pub fn tile_type(code: usize) -> TileType {
+    match code {
+        BLOCK => TileType::Block,
+        ONE_WAY => TileType::OneWay,
+        LADDER => TileType::Ladder,
+        SPIKES => TileType::Spikes,
+        WATER => TileType::Water,
+        SLOPE_UP => TileType::Slope(Slope::Up),
+        SLOPE_DOWN => TileType::Slope(Slope::Down),
+        SLOPE_UP_LOW => TileType::Slope(Slope::UpLow),
+        SLOPE_UP_HIGH => TileType::Slope(Slope::UpHigh),
+        SLOPE_DOWN_HIGH => TileType::Slope(Slope::DownHigh),
+        SLOPE_DOWN_LOW => TileType::Slope(Slope::DownLow),
+        _ => TileType::Empty,
+    }
+}
+
+/// Tiles that can be stood on from above.
+pub fn is_floor(code: usize) -> bool {
+    match tile_type(code) {
+        TileType::Block | TileType::OneWay | TileType::Slope(_) => true,
+        _ => false,
+    }
+}
+
+fn check_border(name: &str, source: &str, terrain_data: &Vec<Vec<usize>>) -> GameResult<()> {
+    if let Some((v, h)) = find_open_border(terrain_data) {
+        let line_ix = source
+            .lines()
+            .enumerate()
+            .filter(|&(_, l)| !l.trim_right().is_empty() && !l.contains(':'))
+            .map(|(ix, _)| ix)
+            .nth(v)
+            .unwrap_or(v);
+        Err(error(name, line_ix, h, "level border must be solid"))
+    } else {
+        Ok(())
+    }
+}
+
+pub fn find_open_border(terrain_data: &Vec<Vec<usize>>) -> Option<(usize, usize)> {
+    let height = terrain_data.len();
+    let width = terrain_data.first().map(|r| r.len()).unwrap_or(0);
+
+    for (v, row) in terrain_data.iter().enumerate() {
+        for (h, tile) in row.iter().enumerate() {
+            let on_border = v == 0 || v == height - 1 || h == 0 || h == width - 1;
+            if on_border && *tile != BLOCK {
+                return Some((v, h));
+            }
+        }
+    }
+    None
+}
+
+fn error(name: &str, line_ix: usize, col_ix: usize, msg: &str) -> GameError {
+    GameError::ResourceLoadError(format!(
+        "{}: line {}, column {}: {}",
+        name,
+        line_ix + 1,
+        col_ix + 1,
+        msg
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(res: GameResult<TileMap>) -> String {
+        match res {
+            Err(GameError::ResourceLoadError(msg)) => msg,
+            _ => panic!("expected a load error"),
+        }
+    }
+
+    #[test]
+    fn parses_tile_grid() {
+        let map = TileMap::parse("test", "seed: 7\n####\n#.=#\n####\n").ok().unwrap();
+        assert_eq!(map.seed, 7);
+        assert_eq!(
+            map.terrain_data,
+            vec![vec![1, 1, 1, 1], vec![1, 0, 2, 1], vec![1, 1, 1, 1]]
+        );
+    }
+
+    #[test]
+    fn parses_special_tiles() {
+        let map = TileMap::parse("test", "#######\n#H^~rR#\n#/\\Ll.#\n#######\n")
+            .ok()
+            .unwrap();
+        assert_eq!(map.terrain_data[1], vec![1, 3, 4, 5, 8, 9, 1]);
+        assert_eq!(map.terrain_data[2], vec![1, 6, 7, 10, 11, 0, 1]);
+        assert_eq!(tile_type(map.terrain_data[2][4]), TileType::Slope(Slope::DownLow));
+    }
+
+    #[test]
+    fn reports_bad_property() {
+        let msg = message(TileMap::parse("test", "seed: many\n####\n"));
+        assert_eq!(msg, "test: line 1, column 7: seed must be an integer");
+    }
+
+    #[test]
+    fn reports_unknown_tile() {
+        let msg = message(TileMap::parse("test", "####\n#.x#\n####\n"));
+        assert_eq!(msg, "test: line 2, column 3: unknown tile code 'x'");
+    }
+
+    #[test]
+    fn reports_ragged_rows() {
+        let msg = message(TileMap::parse("test", "####\n#..\n####\n"));
+        assert_eq!(msg, "test: line 2, column 4: row has 3 tiles, expected 4");
+    }
+
+    #[test]
+    fn reports_missing_border() {
+        let msg = message(TileMap::parse("test", "####\n#...\n####\n"));
+        assert_eq!(msg, "test: line 2, column 4: level border must be solid");
+    }
+}
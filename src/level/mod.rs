@@ -1,12 +1,22 @@
+mod autotile;
+mod decoration;
+mod generator;
 mod index;
 mod loaded;
+mod map;
 mod terrain;
+mod tiled;
 
-use ggez::{Context, GameResult};
+use ggez::{Context, GameError, GameResult};
+use std::collections::HashMap;
 use ggez::graphics::Image;
 use util::Vector2;
 
+use components::MovementProfile;
+use layers;
 use marker::geom::*;
+use physics::Volume;
+use physics::components::{Trigger, TriggerKind};
 use sprite::MarkedTiles;
 
 use ggez::graphics;
@@ -15,53 +25,191 @@ use ggez::graphics::spritebatch::*;
 
 use marker::{Horizontal, Square};
 
+pub use self::decoration::DecorationSettings;
+pub use self::generator::{GeneratedLevel, Generator, Reachability};
 pub use self::loaded::*;
+pub use self::map::TileMap;
 pub use self::terrain::*;
 
+use self::autotile::AutoTiler;
+use self::decoration::Decorator;
 use self::index::LevelAssetIndex;
+use self::tiled::TiledLevel;
 
+/// Size of a terrain tile in world units, for the physics and for planning generated levels.
+pub const TILE_SIZE: f64 = 128.0;
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum LevelType {
-    Graveyard,
+    Theme(String),
+    Tiled(String),
+    Generated { seed: u32, width: usize, height: usize },
+}
+
+impl LevelType {
+    /// Every theme under `/level` with a map or a terrain file, wherever ggez mounted
+    /// the resources from.
+    pub fn discover(ctx: &mut Context) -> GameResult<Vec<LevelType>> {
+        let mut names = vec![];
+
+        for path in ctx.filesystem.read_dir("/level")? {
+            if !ctx.filesystem.is_dir(&path) {
+                continue;
+            }
+            if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+                let (map, terrain) = (path.join("map.json"), path.join("terrain.txt"));
+                if ctx.filesystem.exists(&map) || ctx.filesystem.exists(&terrain) {
+                    names.push(name.to_owned());
+                }
+            }
+        }
+
+        names.sort();
+        Ok(names.into_iter().map(LevelType::Theme).collect())
+    }
+
+    pub fn name(&self) -> &str {
+        match *self {
+            LevelType::Theme(ref name) => name,
+            LevelType::Tiled(ref path) => path,
+            LevelType::Generated { .. } => "generated",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TileMarker {
+    Ground(Square),
+    Platform(Horizontal),
+}
+
+#[derive(Debug, Clone)]
+pub struct Decoration {
+    pub name: String,
+    pub position: Vector2,
+}
+
+/// A trigger rectangle placed in the level, in tiles from the top left like the map.
+#[derive(Debug, Clone)]
+pub struct LevelTrigger {
+    pub kind: TriggerKind,
+    pub name: String,
+    pub position: Vector2,
+    pub size: Vector2,
 }
 
 pub struct Level {
     pub terrain_data: Vec<Vec<usize>>,
+    pub markers: HashMap<(usize, usize), TileMarker>,
+    pub spawns: Vec<Vector2>,
+    pub triggers: Vec<LevelTrigger>,
+    pub decorations: Vec<Decoration>,
+    pub decoration: DecorationSettings,
+    pub seed: u32,
     pub index: LevelAssetIndex,
     pub assets: LoadedAssets,
 }
 
 impl Level {
-    pub fn load(ctx: &mut Context, lt: LevelType) -> GameResult<Level> {
-        let assets = LoadedAssets::load_assets(ctx, lt)?;
-        #[cfg_attr(rustfmt, rustfmt_skip)]
-        let terrain_data = vec![
-            vec![1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 2, 2, 2, 2, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 1, 1, 1, 1, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 2, 2, 2, 2, 2, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 1, 1, 1, 2, 2, 2, 2, 1, 1, 1, 1, 0, 0, 0, 0, 0, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2, 2, 2, 2, 2, 2, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1],
-            vec![1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1],
-        ];
-
-        let index = LevelAssetIndex::build(&assets);
+    /// Generated levels are made for `profile` to get through.
+    pub fn load(ctx: &mut Context, lt: &LevelType, profile: &MovementProfile) -> GameResult<Level> {
+        let tiled = match *lt {
+            LevelType::Theme(ref theme) => {
+                let map_path = format!("/level/{}/map.json", theme);
+                if ctx.filesystem.exists(&map_path) {
+                    TiledLevel::load(ctx, &map_path, theme)?
+                } else {
+                    let map = TileMap::load(ctx, &format!("/level/{}/terrain.txt", theme))?;
+                    TiledLevel {
+                        theme: theme.clone(),
+                        seed: map.seed,
+                        terrain_data: map.terrain_data,
+                        markers: HashMap::new(),
+                        spawns: vec![],
+                        triggers: vec![],
+                        decorations: vec![],
+                        decoration: DecorationSettings::default(),
+                    }
+                }
+            }
+            LevelType::Tiled(ref path) => TiledLevel::load(ctx, path, "graveyard")?,
+            LevelType::Generated {
+                seed,
+                width,
+                height,
+            } => {
+                let generated = match Generator::generate(seed, width, height, TILE_SIZE, profile) {
+                    Some(generated) => generated,
+                    None => {
+                        return Err(GameError::ResourceLoadError(format!(
+                            "Couldn't generate a completable {}x{} level from seed {}",
+                            width,
+                            height,
+                            seed
+                        )))
+                    }
+                };
+                let (h, v) = generated.spawn;
+                let (eh, ev) = generated.exit;
+                TiledLevel {
+                    theme: "graveyard".to_owned(),
+                    seed,
+                    terrain_data: generated.terrain_data,
+                    markers: HashMap::new(),
+                    spawns: vec![Vector2::new(h as f64 + 0.5, v as f64 + 1.0)],
+                    // the exit tile and the one above it, tall enough to walk into
+                    triggers: vec![
+                        LevelTrigger {
+                            kind: TriggerKind::Exit,
+                            name: "exit".to_owned(),
+                            position: Vector2::new(eh as f64, ev as f64 - 1.0),
+                            size: Vector2::new(1.0, 2.0),
+                        },
+                    ],
+                    decorations: vec![],
+                    decoration: DecorationSettings::default(),
+                }
+            }
+        };
+
+        let assets = LoadedAssets::load_assets(ctx, &tiled.theme)?;
+        let index = LevelAssetIndex::build(&assets, tiled.seed);
+
+        for d in tiled.decorations.iter() {
+            if index.find_object_named(&d.name).is_none() {
+                return Err(GameError::ResourceLoadError(format!(
+                    "Unknown decoration '{}' for theme {}",
+                    d.name,
+                    tiled.theme
+                )));
+            }
+        }
+
+        for (v, row) in tiled.terrain_data.iter().enumerate() {
+            for (h, &code) in row.iter().enumerate() {
+                if let Some((name, _)) = special_sprite(code) {
+                    if find_special(&index, code, h, v).is_none() {
+                        return Err(GameError::ResourceLoadError(format!(
+                            "Theme {} has no '{}' sprite or ground tile for tile {}, {}",
+                            tiled.theme,
+                            name,
+                            h,
+                            v
+                        )));
+                    }
+                }
+            }
+        }
 
         Ok(Level {
             index,
-            terrain_data,
+            terrain_data: tiled.terrain_data,
+            markers: tiled.markers,
+            spawns: tiled.spawns,
+            triggers: tiled.triggers,
+            decorations: tiled.decorations,
+            decoration: tiled.decoration,
+            seed: tiled.seed,
             assets,
         })
     }
@@ -72,22 +220,26 @@ pub struct RenderableLevel {
     pub ground_batch: SpriteBatch,
     pub objects_batch: SpriteBatch,
     pub terrain: Terrain,
+    pub spawns: Vec<Vector2>,
+    pub triggers: Vec<Trigger>,
 }
 
 impl RenderableLevel {
     pub fn build(level: Level) -> RenderableLevel {
         let index: LevelAssetIndex;
         let terrain_data: Vec<Vec<usize>>;
+        let markers: HashMap<(usize, usize), TileMarker>;
         let assets: LoadedAssets;
 
         {
             index = level.index;
             terrain_data = level.terrain_data;
+            markers = level.markers;
             assets = level.assets;
         }
 
         let mut g_batch = SpriteBatch::new(assets.ground.image);
-        let o_batch = SpriteBatch::new(assets.objects.image);
+        let mut o_batch = SpriteBatch::new(assets.objects.image);
         let bg = assets.background;
 
         let mut terrain_vec: Vec<Vec<TileType>> = vec![];
@@ -100,179 +252,230 @@ impl RenderableLevel {
             let mut h_vec = vec![];
 
             for tile in v_vec.iter() {
-                match tile {
-                    &0 => h_vec.push(TileType::Empty),
-                    &1 => h_vec.push(TileType::Block),
-                    &2 => h_vec.push(TileType::OneWay),
-                    _ => h_vec.push(TileType::Empty),
-                }
+                h_vec.push(map::tile_type(*tile));
             }
 
             h_vec.shrink_to_fit();
             terrain_vec.push(h_vec);
         }
 
-        {
-            let is_left_wall = |h| h == 0;
-            let is_right_wall = |h| h == width - 1;
-            let is_floor = |v| v == height - 1;
-            let is_roof = |v| v == 0;
-            let is_corner =
-                |h, v| (is_left_wall(h) || is_right_wall(h)) && (is_floor(v) || is_roof(v));
-
-            let lookup = |(h, v, t): (usize, usize, &Vec<Vec<usize>>)| -> Option<Rect> {
-                let it = t[v][h];
-                if it == 0 {
-                    None
+        for h in 0..width {
+            for v in 0..height {
+                let t = &terrain_data;
+                let rect: Option<Rect> = if let Some(marker) = markers.get(&(h, v)) {
+                    match *marker {
+                        TileMarker::Ground(ref sqr) => index.find_ground(sqr.clone(), h, v),
+                        TileMarker::Platform(ref hor) => index.find_platform(hor.clone(), h, v),
+                    }
                 } else {
-                    let itself = t[v][h];
-                    let above = t[v - 1][h];
-                    let below = t[v + 1][h];
-                    let on_left = t[v][h - 1];
-                    let on_right = t[v][h + 1];
-                    let above_right = t[v - 1][h + 1];
-                    let above_left = t[v - 1][h - 1];
-                    let bottom_left = t[v + 1][h - 1];
-                    let bottom_right = t[v + 1][h + 1];
-
-                    let mat = (
-                        (above_left, above, above_right),
-                        (on_left, itself, on_right),
-                        (bottom_left, below, bottom_right),
-                    );
-
-                    if it == 1 {
-                        match mat {
-                            ((l, 1, r), (1, 1, 1), (_, 1, _)) => {
-                                if l != 0 && r != 0 {
-                                    index.find_ground(Square::MM)
-                                } else if r != 0 {
-                                    index.find_ground(Square::IBR)
-                                } else {
-                                    index.find_ground(Square::IBL)
-                                }
-                            }
-                            ((l, 0, r), (1, 1, 1), (_, 1, _)) => {
-                                if l != 0 {
-                                    index.find_ground(Square::ILT)
-                                } else if r != 0 {
-                                    index.find_ground(Square::IRT)
-                                } else {
-                                    index.find_ground(Square::MT)
-                                }
-                            }
-                            ((_, a, _), (l, 1, r), (_, b, _)) => {
-                                if a == 0 {
-                                    if l == 0 {
-                                        index.find_ground(Square::LT)
-                                    } else if r == 0 {
-                                        index.find_ground(Square::RT)
-                                    } else {
-                                        index.find_ground(Square::MT)
-                                    }
-                                } else if b == 0 {
-                                    if l == 0 {
-                                        index.find_ground(Square::LB)
-                                    } else if r == 0 {
-                                        index.find_ground(Square::RB)
-                                    } else {
-                                        index.find_ground(Square::MB)
-                                    }
-                                } else {
-                                    if l == 0 {
-                                        index.find_ground(Square::LM)
-                                    } else if r == 0 {
-                                        index.find_ground(Square::RM)
-                                    } else {
-                                        index.find_ground(Square::MM)
-                                    }
-                                }
-                            }
-                            _ => None,
+                    match t[v][h] {
+                        map::BLOCK => {
+                            let sqr = AutoTiler::ground(AutoTiler::neighbours(t, h, v));
+                            index.find_ground(sqr, h, v)
                         }
-                    } else if it == 2 {
-                        if on_left != 0 && on_right != 0 {
-                            index.find_platform(Horizontal::Center)
-                        } else if on_left == 0 {
-                            index.find_platform(Horizontal::Left)
-                        } else if on_right == 0 {
-                            index.find_platform(Horizontal::Right)
-                        } else {
-                            index.find_platform(Horizontal::Center)
+                        map::ONE_WAY => {
+                            let hor = AutoTiler::platform(t[v][h - 1], t[v][h + 1]);
+                            index.find_platform(hor, h, v)
                         }
-                    } else {
-                        None
+                        _ => None,
                     }
-                }
-            };
-
-            let t: &Vec<Vec<usize>> = &terrain_data;
-
-            for h in 0..width {
-                for v in 0..height {
-                    let rect: Option<Rect> = if is_left_wall(h) && is_floor(v) {
-                        index.find_ground(Square::IBL)
-                    } else if is_floor(v) && h == 1 {
-                        index.find_ground(Square::ILT)
-                    } else if is_floor(v) && h == (width - 2) {
-                        index.find_ground(Square::IRT)
-                    } else if is_right_wall(h) && is_floor(v) {
-                        index.find_ground(Square::IBR)
-                    } else if is_corner(h, v) {
-                        index.find_ground(Square::MM)
-                    } else if is_left_wall(h) {
-                        if t[v][h + 1] != 0 {
-                            index.find_ground(Square::MM)
-                        } else {
-                            index.find_ground(Square::RM)
-                        }
-                    } else if is_right_wall(h) {
-                        if t[v][h - 1] != 0 {
-                            index.find_ground(Square::MM)
-                        } else {
-                            index.find_ground(Square::LM)
-                        }
-                    } else if is_floor(v) {
-                        if t[v - 1][h] != 0 {
-                            index.find_ground(Square::MM)
-                        } else {
-                            index.find_ground(Square::MT)
-                        }
-                    } else if is_roof(v) {
-                        index.find_ground(Square::MB)
-                    } else {
-                        lookup((h, v, t))
-                    };
+                };
 
-                    if let Some(rect) = rect {
-                        let dp = DrawParam {
-                            src: graphics::Rect::from(rect),
-                            dest: graphics::Point2::new(
-                                (h * 128) as f32,
-                                -((pixel_height - v * 128) as f32),
-                            ),
-                            scale: graphics::Point2::new(1.0, 1.0),
+                let rect = match find_special(&index, t[v][h], h, v) {
+                    Some(SpecialSprite::Object(sprite, flip)) => {
+                        let x = (h * 128) as f32 + if flip { 128.0 } else { 0.0 };
+                        o_batch.add(DrawParam {
+                            src: graphics::Rect::from(sprite),
+                            dest: graphics::Point2::new(x, -((pixel_height - v * 128) as f32)),
+                            scale: graphics::Point2::new(if flip { -1.0 } else { 1.0 }, 1.0),
                             ..Default::default()
-                        };
+                        });
+                        rect
+                    }
+                    Some(SpecialSprite::Ground(tile)) => Some(tile),
+                    None => rect,
+                };
 
-                        g_batch.add(dp);
+                if let Some(rect) = rect {
+                    let dp = DrawParam {
+                        src: graphics::Rect::from(rect),
+                        dest: graphics::Point2::new(
+                            (h * 128) as f32,
+                            -((pixel_height - v * 128) as f32),
+                        ),
+                        scale: graphics::Point2::new(1.0, 1.0),
+                        ..Default::default()
                     };
-                }
+
+                    g_batch.add(dp);
+                };
             }
-        };
+        }
+
+        for d in level.decorations.iter() {
+            if let Some((rect, size)) = index.find_object_named(&d.name) {
+                let dp = DrawParam {
+                    src: graphics::Rect::from(rect),
+                    dest: graphics::Point2::new(
+                        (d.position.x * 128.0) as f32 - size.w / 2.0,
+                        (d.position.y * 128.0) as f32 - pixel_height as f32 - size.h,
+                    ),
+                    scale: graphics::Point2::new(1.0, 1.0),
+                    ..Default::default()
+                };
+
+                o_batch.add(dp);
+            }
+        }
+
+        let spawns = level
+            .spawns
+            .iter()
+            .map(|s| {
+                Vector2::new(
+                    s.x * 128.0 - 64.0,
+                    (height as f64 - s.y) * 128.0 + 64.0,
+                )
+            })
+            .collect();
+
+        let triggers = level
+            .triggers
+            .into_iter()
+            .map(|t| {
+                let volume = Volume::new(
+                    t.position.x * 128.0 - 64.0,
+                    (height as f64 - t.position.y - t.size.y) * 128.0 + 64.0,
+                    t.size.x * 128.0,
+                    t.size.y * 128.0,
+                );
+                Trigger::new(t.kind, t.name, volume, layers::TRIGGERS)
+            })
+            .collect();
+
+        let decorations = Decorator::decorate(
+            &terrain_data,
+            &index,
+            &level.decoration,
+            level.seed,
+            128.0,
+        );
+        for &(ref rect, ref at) in decorations.ground.iter() {
+            g_batch.add(DrawParam {
+                src: graphics::Rect::from(rect.clone()),
+                dest: graphics::Point2::new(at.x, at.y),
+                scale: graphics::Point2::new(1.0, 1.0),
+                ..Default::default()
+            });
+        }
+        for &(ref rect, ref at) in decorations.surface.iter() {
+            o_batch.add(DrawParam {
+                src: graphics::Rect::from(rect.clone()),
+                dest: graphics::Point2::new(at.x, at.y),
+                scale: graphics::Point2::new(1.0, 1.0),
+                ..Default::default()
+            });
+        }
 
         terrain_vec.reverse();
         RenderableLevel {
             background: bg,
             ground_batch: g_batch,
             objects_batch: o_batch,
+            spawns,
+            triggers,
             terrain: Terrain {
                 terrain: terrain_vec,
-                position: Vector2::new(0.0, 128.0),
+                position: Vector2::new(0.0, TILE_SIZE),
                 width: width,
                 height: height,
-                tile_size: 128.0,
+                tile_size: TILE_SIZE,
             },
         }
     }
 }
+
+/// How a special tile is drawn, see `find_special`.
+enum SpecialSprite {
+    Object(Rect, bool),
+    Ground(Rect),
+}
+
+/// The theme's named object for the tile, or a ground sheet tile standing in for it
+/// when the theme has none.
+fn find_special(index: &LevelAssetIndex, code: usize, h: usize, v: usize) -> Option<SpecialSprite> {
+    let (name, flip) = match special_sprite(code) {
+        Some(sprite) => sprite,
+        None => return None,
+    };
+    if let Some((rect, _)) = index.find_object_named(name) {
+        return Some(SpecialSprite::Object(rect, flip));
+    }
+    let tile = match special_fallback(code) {
+        TileMarker::Ground(sqr) => index.find_ground(sqr, h, v),
+        TileMarker::Platform(hor) => index.find_platform(hor, h, v),
+    };
+    tile.map(SpecialSprite::Ground)
+}
+
+fn special_fallback(code: usize) -> TileMarker {
+    match code {
+        map::LADDER => TileMarker::Platform(Horizontal::Center),
+        _ => TileMarker::Ground(Square::MT),
+    }
+}
+
+// Themes draw the special tiles with these named objects, slopes are drawn rising
+// to the right and get mirrored for the falling ones.
+fn special_sprite(code: usize) -> Option<(&'static str, bool)> {
+    match code {
+        map::LADDER => Some(("Ladder", false)),
+        map::SPIKES => Some(("Spikes", false)),
+        map::WATER => Some(("Water", false)),
+        map::SLOPE_UP => Some(("Slope", false)),
+        map::SLOPE_DOWN => Some(("Slope", true)),
+        map::SLOPE_UP_LOW => Some(("SlopeLow", false)),
+        map::SLOPE_UP_HIGH => Some(("SlopeHigh", false)),
+        map::SLOPE_DOWN_HIGH => Some(("SlopeHigh", true)),
+        map::SLOPE_DOWN_LOW => Some(("SlopeLow", true)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use marker::{SpriteData, SpriteType};
+
+    fn sprite(name: &str, markers: SpriteType) -> SpriteData {
+        SpriteData {
+            on_screen_frame: Rect::new(0.0, 0.0, 0.5, 0.5),
+            frame: Rect::new(0.0, 0.0, 128.0, 128.0),
+            markers,
+            name: format!("{}.png", name),
+            index: 0,
+            hitboxes: vec![],
+        }
+    }
+
+    #[test]
+    fn special_tiles_fall_back_to_ground_tiles() {
+        let ground = vec![sprite("Tile (2)", SpriteType::Ground { square: vec![Square::MT] })];
+        let objects = vec![sprite("Ladder", SpriteType::Object)];
+        let index = LevelAssetIndex::from_sprites(&ground, &objects, 1);
+
+        match find_special(&index, map::LADDER, 0, 0) {
+            Some(SpecialSprite::Object(_, false)) => (),
+            _ => panic!("ladder should use the theme's sprite"),
+        }
+        match find_special(&index, map::SPIKES, 0, 0) {
+            Some(SpecialSprite::Ground(_)) => (),
+            _ => panic!("spikes should fall back to a ground tile"),
+        }
+        assert!(find_special(&index, map::BLOCK, 0, 0).is_none());
+
+        // no ladder sprite and no platform tile to stand in for it
+        let bare = LevelAssetIndex::from_sprites(&ground, &[], 1);
+        assert!(find_special(&bare, map::LADDER, 0, 0).is_none());
+    }
+}
@@ -69,12 +69,63 @@ impl Terrain {
 
     pub fn is_one_way_platform(&self, x: isize, y: isize) -> bool {
         if let Some((x, y)) = self.in_bounds(x, y) {
-            self.terrain[y][x] == TileType::OneWay
+            self.terrain[y][x] == TileType::OneWay || self.is_ladder_top(x as isize, y as isize)
         } else {
             false
         }
     }
 
+    pub fn is_ladder(&self, x: isize, y: isize) -> bool {
+        if let Some((x, y)) = self.in_bounds(x, y) {
+            self.terrain[y][x] == TileType::Ladder
+        } else {
+            false
+        }
+    }
+
+    // the topmost ladder tile can be stood on like a one-way platform
+    pub fn is_ladder_top(&self, x: isize, y: isize) -> bool {
+        self.is_ladder(x, y) && !self.is_ladder(x, y + 1)
+    }
+
+    pub fn is_hazard(&self, x: isize, y: isize) -> bool {
+        if let Some((x, y)) = self.in_bounds(x, y) {
+            self.terrain[y][x] == TileType::Spikes
+        } else {
+            false
+        }
+    }
+
+    pub fn is_water(&self, x: isize, y: isize) -> bool {
+        if let Some((x, y)) = self.in_bounds(x, y) {
+            self.terrain[y][x] == TileType::Water
+        } else {
+            false
+        }
+    }
+
+    pub fn get_slope(&self, x: isize, y: isize) -> Option<Slope> {
+        if let Some((x, y)) = self.in_bounds(x, y) {
+            if let TileType::Slope(slope) = self.terrain[y][x] {
+                return Some(slope);
+            }
+        }
+        None
+    }
+
+    /// World y of the slope surface in tile row `y` at world `x`, if that tile is a slope.
+    pub fn get_slope_surface_y(&self, x: f64, y: isize) -> Option<f64> {
+        let tile_x = self.get_tile_x_at_point(x);
+        self.get_slope(tile_x, y).map(|slope| {
+            let left = self.get_map_tile_position(tile_x, y) - Vector2::new(
+                self.tile_size / 2.0,
+                self.tile_size / 2.0,
+            );
+            let along = ((x - left.x) / self.tile_size).max(0.0).min(1.0);
+            left.y + slope.height_at(along) * self.tile_size
+        })
+    }
+
     pub fn is_empty(&self, x: isize, y: isize) -> bool {
         if let Some((x, y)) = self.in_bounds(x, y) {
             self.terrain[y][x] == TileType::Empty
@@ -89,4 +140,86 @@ pub enum TileType {
     Empty,
     Block,
     OneWay,
+    Ladder,
+    Spikes,
+    Water,
+    Slope(Slope),
+}
+
+/// Slopes are named by the direction they rise to when walking right. 22.5° slopes
+/// span two tiles, the `Low` half and the `High` half.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Slope {
+    Up,
+    Down,
+    UpLow,
+    UpHigh,
+    DownHigh,
+    DownLow,
+}
+
+impl Slope {
+    /// Height of the surface as a fraction of the tile, `along` going from the left edge (0) to the right one (1).
+    pub fn height_at(&self, along: f64) -> f64 {
+        match *self {
+            Slope::Up => along,
+            Slope::Down => 1.0 - along,
+            Slope::UpLow => along / 2.0,
+            Slope::UpHigh => 0.5 + along / 2.0,
+            Slope::DownHigh => 1.0 - along / 2.0,
+            Slope::DownLow => 0.5 - along / 2.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terrain(rows: Vec<Vec<TileType>>) -> Terrain {
+        Terrain {
+            width: rows[0].len(),
+            height: rows.len(),
+            terrain: rows,
+            position: Vector2::new(0.0, 128.0),
+            tile_size: 128.0,
+        }
+    }
+
+    #[test]
+    fn slope_surface_follows_the_tile() {
+        let t = terrain(vec![
+            vec![TileType::Block; 4],
+            vec![
+                TileType::Slope(Slope::Up),
+                TileType::Slope(Slope::UpLow),
+                TileType::Slope(Slope::UpHigh),
+                TileType::Empty,
+            ],
+        ]);
+
+        // row 1 spans world y 192..320, column 0 spans world x -64..64
+        assert_eq!(t.get_slope_surface_y(-64.0, 1), Some(192.0));
+        assert_eq!(t.get_slope_surface_y(0.0, 1), Some(256.0));
+        assert_eq!(t.get_slope_surface_y(128.0, 1), Some(192.0 + 32.0));
+        assert_eq!(t.get_slope_surface_y(256.0, 1), Some(192.0 + 96.0));
+        assert_eq!(t.get_slope_surface_y(384.0, 1), None);
+        assert_eq!(t.get_slope_surface_y(0.0, 0), None);
+    }
+
+    #[test]
+    fn ladder_top_is_a_platform() {
+        let t = terrain(vec![
+            vec![TileType::Block; 2],
+            vec![TileType::Ladder, TileType::Water],
+            vec![TileType::Ladder, TileType::Spikes],
+        ]);
+
+        assert!(!t.is_one_way_platform(0, 1));
+        assert!(t.is_one_way_platform(0, 2));
+        assert!(t.is_ladder(0, 1));
+        assert!(t.is_water(1, 1));
+        assert!(t.is_hazard(1, 2));
+        assert!(!t.is_obstacle(1, 2));
+    }
 }
@@ -0,0 +1,375 @@
+use ggez::{Context, GameError, GameResult};
+use serde_json;
+use serde_json::Value;
+use std::collections::HashMap;
+use util::Vector2;
+
+use super::{Decoration, DecorationSettings, LevelTrigger, TileMarker};
+use super::map;
+use marker::{Horizontal, Square};
+use physics::components::TriggerKind;
+
+const FLIP_FLAGS: u32 = 0xE000_0000;
+
+#[derive(Deserialize)]
+struct TiledMap {
+    width: usize,
+    height: usize,
+    tilewidth: f64,
+    tileheight: f64,
+    layers: Vec<TiledLayer>,
+    tilesets: Vec<TiledTileset>,
+    /// An object of name to value before Tiled 1.2, an array of `{name, type, value}` since.
+    #[serde(default)]
+    properties: Value,
+}
+
+#[derive(Deserialize)]
+struct TiledLayer {
+    #[serde(rename = "type")]
+    tpe: String,
+    #[serde(default)]
+    data: Vec<u32>,
+    #[serde(default)]
+    objects: Vec<TiledObject>,
+}
+
+#[derive(Deserialize)]
+struct TiledObject {
+    #[serde(default)]
+    name: String,
+    #[serde(rename = "type", default)]
+    tpe: String,
+    x: f64,
+    y: f64,
+    #[serde(default)]
+    width: f64,
+    #[serde(default)]
+    height: f64,
+    gid: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct TiledTileset {
+    firstgid: u32,
+    source: Option<String>,
+    #[serde(default)]
+    tileproperties: HashMap<String, HashMap<String, Value>>,
+    /// Since Tiled 1.2 the tile properties are here, `[{id, properties}]`.
+    #[serde(default)]
+    tiles: Value,
+}
+
+#[derive(Clone)]
+struct TileDef {
+    code: usize,
+    marker: Option<TileMarker>,
+}
+
+pub struct TiledLevel {
+    pub theme: String,
+    pub seed: u32,
+    pub terrain_data: Vec<Vec<usize>>,
+    pub markers: HashMap<(usize, usize), TileMarker>,
+    pub spawns: Vec<Vector2>,
+    pub triggers: Vec<LevelTrigger>,
+    pub decorations: Vec<Decoration>,
+    pub decoration: DecorationSettings,
+}
+
+impl TiledLevel {
+    pub fn load(ctx: &mut Context, path: &str, default_theme: &str) -> GameResult<TiledLevel> {
+        let file = ctx.filesystem.open(path)?;
+        let tiled: TiledMap = serde_json::from_reader(file).map_err(|e| {
+            GameError::ResourceLoadError(format!("{}: {}", path, e))
+        })?;
+
+        TiledLevel::from_map(path, tiled, default_theme)
+    }
+
+    fn from_map(path: &str, tiled: TiledMap, default_theme: &str) -> GameResult<TiledLevel> {
+        let err = |msg: String| GameError::ResourceLoadError(format!("{}: {}", path, msg));
+
+        let map_properties = properties(&tiled.properties).map_err(|e| err(format!("map {}", e)))?;
+        let theme = match map_properties.get("theme") {
+            Some(&Value::String(ref t)) => t.clone(),
+            Some(_) => return Err(err("map property 'theme' must be a string".to_owned())),
+            None => default_theme.to_owned(),
+        };
+
+        let seed = match map_properties.get("seed") {
+            Some(seed) => seed.as_u64()
+                .ok_or_else(|| err("map property 'seed' must be an integer".to_owned()))?
+                as u32,
+            None => 0,
+        };
+
+        let mut decoration = DecorationSettings::default();
+        if let Some(density) = map_properties.get("decoration_density") {
+            decoration.density = density.as_f64()
+                .ok_or_else(|| err("map property 'decoration_density' must be a number".to_owned()))?;
+        }
+
+        let mut defs: HashMap<u32, TileDef> = HashMap::new();
+        for ts in tiled.tilesets.iter() {
+            if let Some(ref source) = ts.source {
+                return Err(err(format!(
+                    "external tileset '{}' is not supported, embed it into the map",
+                    source
+                )));
+            }
+            for (id, props) in ts.tileproperties.iter() {
+                let id: u32 = id.parse().map_err(|_| err(format!("invalid tile id '{}'", id)))?;
+                let def = tile_def(props).map_err(|e| err(format!("tile {}: {}", id, e)))?;
+                defs.insert(ts.firstgid + id, def);
+            }
+            // older maps use `tiles` for per-tile images keyed by id, that's not for us
+            if let Value::Array(ref tiles) = ts.tiles {
+                for tile in tiles.iter() {
+                    let id = tile["id"]
+                        .as_u64()
+                        .ok_or_else(|| err("tile without an id".to_owned()))?;
+                    let props = match tile.get("properties") {
+                        Some(props) => properties(props),
+                        None => continue,
+                    };
+                    let def = props
+                        .and_then(|props| tile_def(&props))
+                        .map_err(|e| err(format!("tile {}: {}", id, e)))?;
+                    defs.insert(ts.firstgid + id as u32, def);
+                }
+            }
+        }
+
+        let (width, height) = (tiled.width, tiled.height);
+        let mut terrain_data = vec![vec![map::EMPTY; width]; height];
+        let mut markers = HashMap::new();
+        let mut spawns = vec![];
+        let mut triggers = vec![];
+        let mut decorations = vec![];
+
+        for layer in tiled.layers.iter() {
+            match layer.tpe.as_str() {
+                "tilelayer" => {
+                    if layer.data.len() != width * height {
+                        return Err(err(format!(
+                            "tile layer has {} tiles, expected {}",
+                            layer.data.len(),
+                            width * height
+                        )));
+                    }
+                    for (i, gid) in layer.data.iter().enumerate() {
+                        let gid = gid & !FLIP_FLAGS;
+                        if gid == 0 {
+                            continue;
+                        }
+                        let (h, v) = (i % width, i / width);
+                        let def = defs.get(&gid).ok_or_else(|| {
+                            err(format!("tile {}, {}: tile {} has no 'tile' property", h, v, gid))
+                        })?;
+                        terrain_data[v][h] = def.code;
+                        if let Some(ref marker) = def.marker {
+                            markers.insert((h, v), marker.clone());
+                        }
+                    }
+                }
+                "objectgroup" => for o in layer.objects.iter() {
+                    let bottom = if o.gid.is_some() { o.y } else { o.y + o.height };
+                    let at = Vector2::new(
+                        (o.x + o.width / 2.0) / tiled.tilewidth,
+                        bottom / tiled.tileheight,
+                    );
+                    match o.tpe.as_str() {
+                        "spawn" => spawns.push(at),
+                        "decoration" => decorations.push(Decoration {
+                            name: o.name.clone(),
+                            position: at,
+                        }),
+                        other => if let Some(kind) = TriggerKind::from_name(other) {
+                            if o.width <= 0.0 || o.height <= 0.0 {
+                                return Err(err(format!("{} '{}' needs a size", other, o.name)));
+                            }
+                            triggers.push(LevelTrigger {
+                                kind,
+                                name: o.name.clone(),
+                                position: Vector2::new(
+                                    o.x / tiled.tilewidth,
+                                    o.y / tiled.tileheight,
+                                ),
+                                size: Vector2::new(
+                                    o.width / tiled.tilewidth,
+                                    o.height / tiled.tileheight,
+                                ),
+                            });
+                        },
+                    }
+                },
+                _ => (),
+            }
+        }
+
+        if let Some((v, h)) = map::find_open_border(&terrain_data) {
+            return Err(err(format!("tile {}, {}: level border must be solid", h, v)));
+        }
+
+        Ok(TiledLevel {
+            theme,
+            seed,
+            terrain_data,
+            markers,
+            spawns,
+            triggers,
+            decorations,
+            decoration,
+        })
+    }
+}
+
+/// Custom properties by name, from either of the formats Tiled has written them in.
+fn properties(raw: &Value) -> Result<HashMap<String, Value>, String> {
+    match *raw {
+        Value::Null => Ok(HashMap::new()),
+        Value::Object(ref props) => Ok(props.iter().map(|(k, v)| (k.clone(), v.clone())).collect()),
+        Value::Array(ref props) => props
+            .iter()
+            .map(|p| match (p.get("name").and_then(|n| n.as_str()), p.get("value")) {
+                (Some(name), Some(value)) => Ok((name.to_owned(), value.clone())),
+                _ => Err(format!("property {} needs a name and a value", p)),
+            })
+            .collect(),
+        _ => Err(format!("properties must be an object or an array, not {}", raw)),
+    }
+}
+
+fn tile_def(props: &HashMap<String, Value>) -> Result<TileDef, String> {
+    let code = match props.get("tile").and_then(|v| v.as_str()) {
+        Some("empty") => map::EMPTY,
+        Some("block") => map::BLOCK,
+        Some("one_way") => map::ONE_WAY,
+        Some("ladder") => map::LADDER,
+        Some("spikes") => map::SPIKES,
+        Some("water") => map::WATER,
+        Some("slope_up") => map::SLOPE_UP,
+        Some("slope_down") => map::SLOPE_DOWN,
+        Some("slope_up_low") => map::SLOPE_UP_LOW,
+        Some("slope_up_high") => map::SLOPE_UP_HIGH,
+        Some("slope_down_high") => map::SLOPE_DOWN_HIGH,
+        Some("slope_down_low") => map::SLOPE_DOWN_LOW,
+        Some(other) => return Err(format!("unknown tile type '{}'", other)),
+        None => return Err("missing 'tile' property".to_owned()),
+    };
+
+    let marker = if let Some(sqr) = props.get("square") {
+        let sqr: Square = serde_json::from_value(sqr.clone())
+            .map_err(|_| format!("unknown square marker {}", sqr))?;
+        Some(TileMarker::Ground(sqr))
+    } else if let Some(hor) = props.get("horizontal") {
+        let hor: Horizontal = serde_json::from_value(hor.clone())
+            .map_err(|_| format!("unknown horizontal marker {}", hor))?;
+        Some(TileMarker::Platform(hor))
+    } else {
+        None
+    };
+
+    Ok(TileDef { code, marker })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAP: &str = r#"{
+        "width": 3, "height": 3, "tilewidth": 32, "tileheight": 32,
+        "properties": { "theme": "graveyard", "seed": 42 },
+        "tilesets": [{
+            "firstgid": 1,
+            "tileproperties": {
+                "0": { "tile": "block" },
+                "1": { "tile": "one_way", "horizontal": "Center" }
+            }
+        }],
+        "layers": [
+            { "type": "tilelayer", "data": [1, 1, 1, 1, 2, 1, 1, 1, 1] },
+            { "type": "objectgroup", "objects": [
+                { "type": "spawn", "x": 32, "y": 32, "width": 32, "height": 32 },
+                { "type": "decoration", "name": "Crate", "gid": 3, "x": 32, "y": 64 },
+                { "type": "exit", "name": "door", "x": 64, "y": 16, "width": 32, "height": 48 }
+            ]}
+        ]
+    }"#;
+
+    #[test]
+    fn converts_tiled_map() {
+        let tiled: TiledMap = serde_json::from_str(MAP).unwrap();
+        let level = TiledLevel::from_map("test", tiled, "none").ok().unwrap();
+
+        assert_eq!(level.theme, "graveyard");
+        assert_eq!(level.seed, 42);
+        assert_eq!(level.terrain_data, vec![vec![1, 1, 1], vec![1, 2, 1], vec![1, 1, 1]]);
+        match level.markers.get(&(1, 1)) {
+            Some(&TileMarker::Platform(Horizontal::Center)) => (),
+            other => panic!("unexpected marker {:?}", other),
+        }
+        assert_eq!(level.spawns, vec![Vector2::new(1.5, 2.0)]);
+        assert_eq!(level.decorations[0].name, "Crate");
+        assert_eq!(level.decorations[0].position, Vector2::new(1.0, 2.0));
+        assert_eq!(level.triggers[0].kind, TriggerKind::Exit);
+        assert_eq!(level.triggers[0].name, "door");
+        assert_eq!(level.triggers[0].position, Vector2::new(2.0, 0.5));
+        assert_eq!(level.triggers[0].size, Vector2::new(1.0, 1.5));
+    }
+
+    // what Tiled 1.2 and later saves
+    const MODERN_MAP: &str = r#"{
+        "width": 3, "height": 3, "tilewidth": 32, "tileheight": 32,
+        "type": "map", "version": 1.2, "tiledversion": "1.2.1", "orientation": "orthogonal",
+        "properties": [
+            { "name": "theme", "type": "string", "value": "graveyard" },
+            { "name": "seed", "type": "int", "value": 42 },
+            { "name": "decoration_density", "type": "float", "value": 0.5 }
+        ],
+        "tilesets": [{
+            "firstgid": 1, "name": "tiles", "tilecount": 3, "columns": 3,
+            "tiles": [
+                { "id": 0, "properties": [
+                    { "name": "tile", "type": "string", "value": "block" }
+                ]},
+                { "id": 1, "properties": [
+                    { "name": "tile", "type": "string", "value": "one_way" },
+                    { "name": "horizontal", "type": "string", "value": "Center" }
+                ]},
+                { "id": 2, "type": "decoration" }
+            ]
+        }],
+        "layers": [
+            { "type": "tilelayer", "name": "terrain", "width": 3, "height": 3,
+              "data": [1, 1, 1, 1, 2, 1, 1, 1, 1] },
+            { "type": "objectgroup", "name": "objects", "objects": [
+                { "id": 1, "type": "spawn", "x": 32, "y": 32, "width": 32, "height": 32 }
+            ]}
+        ]
+    }"#;
+
+    #[test]
+    fn converts_modern_tiled_map() {
+        let tiled: TiledMap = serde_json::from_str(MODERN_MAP).unwrap();
+        let level = TiledLevel::from_map("test", tiled, "none").ok().unwrap();
+
+        assert_eq!(level.theme, "graveyard");
+        assert_eq!(level.seed, 42);
+        assert_eq!(level.decoration.density, 0.5);
+        assert_eq!(level.terrain_data, vec![vec![1, 1, 1], vec![1, 2, 1], vec![1, 1, 1]]);
+        match level.markers.get(&(1, 1)) {
+            Some(&TileMarker::Platform(Horizontal::Center)) => (),
+            other => panic!("unexpected marker {:?}", other),
+        }
+        assert_eq!(level.spawns, vec![Vector2::new(1.5, 2.0)]);
+    }
+
+    #[test]
+    fn rejects_nameless_properties() {
+        let map = MODERN_MAP.replace(r#""name": "seed", "#, "");
+        let tiled: TiledMap = serde_json::from_str(&map).unwrap();
+        assert!(TiledLevel::from_map("test", tiled, "none").is_err());
+    }
+}
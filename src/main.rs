@@ -26,6 +26,11 @@ mod level;
 mod game;
 mod rendering;
 mod player;
+mod combat;
+mod ai;
+mod input;
+mod replay;
+mod layers;
 mod marker;
 
 pub use rendering::asset_storage;
@@ -34,6 +39,8 @@ pub use rendering::camera;
 use game::*;
 use ggez::{Context, event, graphics};
 use ggez::conf::*;
+use level::LevelType;
+use std::env;
 
 // use cpuprofiler::PROFILER;
 
@@ -47,6 +54,16 @@ fn main() {
     let ctx = &mut Context::load_from_conf("config", "me", c).unwrap();
     graphics::set_default_filter(ctx, graphics::FilterMode::Nearest);
 
-    let mut state = Game::new(ctx).unwrap();
+    let mut levels = LevelType::discover(ctx).unwrap();
+    if let Some(path) = env::args().nth(1) {
+        levels.insert(0, LevelType::Tiled(path));
+    }
+    levels.push(LevelType::Generated {
+        seed: rand::random(),
+        width: 64,
+        height: 24,
+    });
+
+    let mut state = Game::new(ctx, levels).unwrap();
     event::run(ctx, &mut state).unwrap();
 }
@@ -53,4 +53,7 @@ pub struct SpriteData {
     pub markers: SpriteType,
     pub name: String,
     pub index: usize,
+    /// Areas this frame hits, in pixels of `frame`.
+    #[serde(default)]
+    pub hitboxes: Vec<geom::Rect>,
 }
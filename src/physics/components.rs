@@ -37,10 +37,29 @@ pub struct HasAABB {
     pub was_at_ceiling: bool,
     pub at_ceiling: bool,
 
+    pub on_slope: bool,
+    pub on_ladder: bool,
+    pub above_ladder: bool,
+    pub in_water: bool,
+    pub touches_hazard: bool,
+    /// Stood on another body at the last collision check, counts as ground.
+    pub on_entity: bool,
+
+    /// Set by whoever moves the object along a ladder, one-way platforms are ignored meanwhile.
+    pub climbing: bool,
+    /// Top of the one-way platform being dropped through, see `HumanoidMovement::drop_through`.
+    pub dropping_through: Option<f64>,
+    pub ledge: Option<Ledge>,
+
     pub cannot_go_left_frames: usize,
     pub cannot_go_right_frames: usize,
 
-    pub frames_from_jump_start: usize,
+    /// Game time the object last stood on the ground.
+    pub grounded_at: f64,
+    /// Game time of the last jump, jump presses before it are used up.
+    pub jumped_at: Option<f64>,
+    /// The rise comes from a jump press, letting go of the button cuts it short.
+    pub input_jump: bool,
 }
 
 impl MovingObject {
@@ -70,13 +89,42 @@ impl HasAABB {
             on_platform: false,
             was_at_ceiling: false,
             at_ceiling: false,
+            on_slope: false,
+            on_ladder: false,
+            above_ladder: false,
+            in_water: false,
+            touches_hazard: false,
+            on_entity: false,
+            climbing: false,
+            dropping_through: None,
+            ledge: None,
             cannot_go_left_frames: 0,
             cannot_go_right_frames: 0,
-            frames_from_jump_start: 0,
+            grounded_at: 0.0,
+            jumped_at: None,
+            input_jump: false,
         }
     }
 }
 
+/// Top corner of a block that can be hung from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Ledge {
+    pub corner: Vector2,
+    pub on_left: bool,
+}
+
+impl Ledge {
+    /// Position of an AABB hanging with its top at the corner, next to the wall.
+    pub fn hang_position(&self, aabb: &AABB) -> Vector2 {
+        let x = if self.on_left {
+            self.corner.x + aabb.half_size.x
+        } else {
+            self.corner.x - aabb.half_size.x
+        };
+        Vector2::new(x - aabb.offset.x, self.corner.y - aabb.half_size.y - aabb.offset.y)
+    }
+}
 
 pub struct HumanoidMovement;
 impl HumanoidMovement {
@@ -113,7 +161,9 @@ impl HumanoidMovement {
                 if terrain.is_obstacle(tile_index_x, tile_index_y) {
                     bb.on_platform = false;
                     return true;
-                } else if terrain.is_one_way_platform(tile_index_x, tile_index_y) &&
+                } else if !bb.climbing &&
+                           bb.dropping_through.map_or(true, |y| *ground_y < y - 1.0) &&
+                           terrain.is_one_way_platform(tile_index_x, tile_index_y) &&
                            (checked_tile.y - *ground_y).abs() <=
                                (HumanoidMovement::PLATFORM_THRESHOLD + mv.old_position.y -
                                     mv.position.y)
@@ -134,6 +184,35 @@ impl HumanoidMovement {
         false
     }
 
+    pub fn has_slope_ground(
+        mv: &MovingObject,
+        bb: &HasAABB,
+        ground_y: &mut f64,
+        terrain: &Terrain,
+    ) -> bool {
+        let foot = mv.position + bb.aabb.offset - Vector2::new(0.0, bb.aabb.half_size.y);
+        let old_foot_y = mv.old_position.y + bb.aabb.offset.y - bb.aabb.half_size.y;
+        // keeps walking objects glued to the slope when going down it
+        let snap = if bb.was_on_ground {
+            terrain.tile_size / 2.0
+        } else {
+            0.0
+        };
+
+        let tile_index_y = terrain.get_tile_y_at_point(foot.y);
+        for &tile_index_y in [tile_index_y, tile_index_y - 1].iter() {
+            if let Some(surface_y) = terrain.get_slope_surface_y(foot.x, tile_index_y) {
+                if foot.y <= surface_y + snap &&
+                    old_foot_y >= surface_y - terrain.tile_size / 2.0
+                {
+                    *ground_y = surface_y;
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
     pub fn has_ceiling(
         mv: &mut MovingObject,
         bb: &mut HasAABB,
@@ -181,8 +260,10 @@ impl HumanoidMovement {
         terrain: &Terrain,
     ) -> bool {
         *wall_x = 0.0;
-        let old_bottom_left = bb.aabb.sensor(&mv.old_position, Sensor::BottomLeft).left().ok();
-        let new_bottom_left = bb.aabb.sensor(&mv.position, Sensor::BottomLeft).left().ok();
+        let step = HumanoidMovement::slope_step(bb, terrain);
+        let old_bottom_left =
+            bb.aabb.sensor(&mv.old_position, Sensor::BottomLeft).left().ok() + step;
+        let new_bottom_left = bb.aabb.sensor(&mv.position, Sensor::BottomLeft).left().ok() + step;
         let mut tile_index_y;
         let end_x = terrain.get_tile_x_at_point(new_bottom_left.x);
         let beg_x = cmp::max(terrain.get_tile_x_at_point(old_bottom_left.x) - 1, end_x);
@@ -193,7 +274,7 @@ impl HumanoidMovement {
                 &old_bottom_left,
                 (end_x - tile_index_x).abs() as f64 / dist as f64,
             );
-            let top_left = bottom_left + Vector2::new(0.0, bb.aabb.half_size.y * 2.0);
+            let top_left = bottom_left + Vector2::new(0.0, bb.aabb.half_size.y * 2.0) - step;
             let mut checked_tile = bottom_left;
             loop {
                 checked_tile.y = checked_tile.y.min(top_left.y);
@@ -219,10 +300,11 @@ impl HumanoidMovement {
         terrain: &Terrain,
     ) -> bool {
         *wall_x = 0.0;
+        let step = HumanoidMovement::slope_step(bb, terrain);
         let old_bottom_right =
-            bb.aabb.sensor(&mv.old_position, Sensor::BottomRight).right().right().ok();
+            bb.aabb.sensor(&mv.old_position, Sensor::BottomRight).right().right().ok() + step;
         let new_bottom_right =
-            bb.aabb.sensor(&mv.position, Sensor::BottomRight).right().right().ok();
+            bb.aabb.sensor(&mv.position, Sensor::BottomRight).right().right().ok() + step;
         let end_x = terrain.get_tile_x_at_point(new_bottom_right.x);
         let beg_x = cmp::min(terrain.get_tile_x_at_point(old_bottom_right.x) + 1, end_x);
         let dist = cmp::max((end_x - beg_x).abs(), 1);
@@ -233,7 +315,7 @@ impl HumanoidMovement {
                 &old_bottom_right,
                 (end_x - tile_index_x).abs() as f64 / dist as f64,
             );
-            let top_right = bottom_right + Vector2::new(0.0, bb.aabb.half_size.y * 2.0);
+            let top_right = bottom_right + Vector2::new(0.0, bb.aabb.half_size.y * 2.0) - step;
             let mut checked_tile = bottom_right;
             loop {
                 checked_tile.y = checked_tile.y.min(top_right.y);
@@ -252,6 +334,94 @@ impl HumanoidMovement {
         false
     }
 
+    /// Lets the object fall through the one-way platform it stands on. Platforms at
+    /// that height stay ignored until the whole AABB is below them.
+    pub fn drop_through(mv: &MovingObject, bb: &mut HasAABB) {
+        if bb.on_platform {
+            let foot_y = mv.position.y + bb.aabb.offset.y - bb.aabb.half_size.y;
+            bb.dropping_through = Some(foot_y);
+        }
+    }
+
+    pub fn update_drop_through(mv: &MovingObject, bb: &mut HasAABB, terrain: &Terrain) {
+        if let Some(platform_y) = bb.dropping_through {
+            let top_y = mv.position.y + bb.aabb.offset.y + bb.aabb.half_size.y;
+            if top_y < platform_y - terrain.tile_size || (bb.on_ground && !bb.on_platform) {
+                bb.dropping_through = None;
+            }
+        }
+    }
+
+    // On a slope the tiles it leads onto are below the feet of the far side, so
+    // the wall sensors start higher up to not treat them as walls.
+    fn slope_step(bb: &HasAABB, terrain: &Terrain) -> Vector2 {
+        if bb.on_slope {
+            Vector2::new(0.0, terrain.tile_size / 2.0)
+        } else {
+            Vector2::new(0.0, 0.0)
+        }
+    }
+
+    /// Tile flags that do not stop the movement: ladders, water and hazards.
+    pub fn update_surroundings(mv: &MovingObject, bb: &mut HasAABB, terrain: &Terrain) {
+        let center = mv.position + bb.aabb.offset;
+        let foot = bb.aabb.sensor(&mv.position, Sensor::BottomLeft).down().ok() +
+            Vector2::new(bb.aabb.half_size.x, 0.0);
+        let center_tile = terrain.get_tile_at_point(center);
+        let foot_tile = terrain.get_tile_at_point(foot);
+
+        bb.on_ladder = terrain.is_ladder(center_tile.x, center_tile.y);
+        bb.above_ladder = terrain.is_ladder(foot_tile.x, foot_tile.y);
+        bb.in_water = terrain.is_water(center_tile.x, center_tile.y);
+
+        let bottom_left = bb.aabb.sensor(&mv.position, Sensor::BottomLeft).ok();
+        let top_right = bb.aabb.sensor(&mv.position, Sensor::TopRight).ok();
+        let from = terrain.get_tile_at_point(bottom_left);
+        let to = terrain.get_tile_at_point(top_right);
+        bb.touches_hazard =
+            (from.x..to.x + 1).any(|x| (from.y..to.y + 1).any(|y| terrain.is_hazard(x, y)));
+    }
+
+    /// A ledge is the top corner of a block the object pushes against, somewhere
+    /// along its upper body, with room to climb up above it.
+    pub fn find_ledge(mv: &MovingObject, bb: &HasAABB, terrain: &Terrain) -> Option<Ledge> {
+        let center = mv.position + bb.aabb.offset;
+        let top_y = center.y + bb.aabb.half_size.y;
+        let own_x = terrain.get_tile_x_at_point(center.x);
+
+        for &on_left in [false, true].iter() {
+            let (pushes, side) = if on_left {
+                (bb.pushes_left_wall, -1.0)
+            } else {
+                (bb.pushes_right_wall, 1.0)
+            };
+            if !pushes {
+                continue;
+            }
+
+            let side_x = center.x + side * (bb.aabb.half_size.x + 1.0);
+            let tile_x = terrain.get_tile_x_at_point(side_x);
+            let from_y = terrain.get_tile_y_at_point(center.y);
+            let to_y = terrain.get_tile_y_at_point(top_y + 1.0);
+            for tile_y in from_y..to_y + 1 {
+                let corner = terrain.get_map_tile_position(tile_x, tile_y) +
+                    Vector2::new(-side * terrain.tile_size / 2.0, terrain.tile_size / 2.0);
+                if corner.y < center.y || corner.y > top_y + 1.0 {
+                    continue;
+                }
+
+                if terrain.is_obstacle(tile_x, tile_y) &&
+                    !terrain.is_obstacle(tile_x, tile_y + 1) &&
+                    !terrain.is_obstacle(tile_x, tile_y + 2) &&
+                    !terrain.is_obstacle(own_x, tile_y + 1)
+                {
+                    return Some(Ledge { corner, on_left });
+                }
+            }
+        }
+        None
+    }
+
     pub const PLATFORM_THRESHOLD: f64 = 2.0;
 }
 
@@ -259,5 +429,190 @@ impl HumanoidMovement {
 #[derive(Debug, Component)]
 #[component(VecStorage)]
 pub struct CollisionDetection {
-    pub group: usize,
+    /// Row and column in `CollisionLayers`.
+    pub layer: usize,
+    /// Share of the push a body takes is the other's mass over both, infinite never moves.
+    pub mass: f64,
+}
+
+/// What a trigger is for, gameplay systems pick the events they care about by it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriggerKind {
+    Checkpoint,
+    Exit,
+    Kill,
+    Camera,
+}
+
+impl TriggerKind {
+    pub fn from_name(name: &str) -> Option<TriggerKind> {
+        match name {
+            "checkpoint" => Some(TriggerKind::Checkpoint),
+            "exit" => Some(TriggerKind::Exit),
+            "kill" => Some(TriggerKind::Kill),
+            "camera" => Some(TriggerKind::Camera),
+            _ => None,
+        }
+    }
+}
+
+/// A sensor volume in world coordinates. Bodies going in and out of it show up in
+/// `TriggerEvents`, it never pushes anything.
+#[derive(Debug, Component)]
+#[component(DenseVecStorage)]
+pub struct Trigger {
+    pub kind: TriggerKind,
+    /// Name of the object in the level, e.g. to tell exits apart.
+    pub name: String,
+    pub volume: Volume,
+    /// Row in `CollisionLayers`, bodies on layers it ignores are not noticed.
+    pub layer: usize,
+    /// Bodies that were inside at the last check.
+    pub inside: Vec<Entity>,
+}
+
+impl Trigger {
+    pub fn new(kind: TriggerKind, name: String, volume: Volume, layer: usize) -> Trigger {
+        Trigger {
+            kind,
+            name,
+            volume,
+            layer,
+            inside: vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use level::TileType;
+
+    // a one-way platform in the middle of an otherwise empty room
+    fn terrain() -> Terrain {
+        let mut rows = vec![vec![TileType::Empty; 3]; 5];
+        rows[0] = vec![TileType::Block; 3];
+        rows[2][1] = TileType::OneWay;
+        Terrain {
+            terrain: rows,
+            position: Vector2::new(0.0, 128.0),
+            width: 3,
+            height: 5,
+            tile_size: 128.0,
+        }
+    }
+
+    fn standing_on_platform() -> (MovingObject, HasAABB) {
+        let bb = HasAABB::new(AABB::new_full(
+            Vector2::new(64.0, 64.0),
+            Vector2::new(1.0, 1.0),
+        ));
+        // platform row 2 top is at 128 + 2 * 128 + 64
+        let mut mv = MovingObject::new(Vector2::new(128.0, 448.0 + 32.0));
+        mv.old_position = mv.position;
+        (mv, bb)
+    }
+
+    fn fall(mv: &mut MovingObject, by: f64) {
+        mv.old_position = mv.position;
+        mv.position.y -= by;
+    }
+
+    #[test]
+    fn stands_on_one_way_platform() {
+        let t = terrain();
+        let (mut mv, mut bb) = standing_on_platform();
+        let mut ground_y = 0.0;
+        fall(&mut mv, 1.0);
+        assert!(HumanoidMovement::has_ground(&mut mv, &mut bb, &mut ground_y, &t));
+        assert!(bb.on_platform);
+        assert_eq!(ground_y, 448.0);
+    }
+
+    #[test]
+    fn drops_through_until_platform_is_cleared() {
+        let t = terrain();
+        let (mut mv, mut bb) = standing_on_platform();
+        let mut ground_y = 0.0;
+        bb.on_platform = true;
+        HumanoidMovement::drop_through(&mv, &mut bb);
+        assert_eq!(bb.dropping_through, Some(448.0));
+
+        bb.on_platform = false;
+        for _ in 0..4 {
+            fall(&mut mv, 20.0);
+            assert!(!HumanoidMovement::has_ground(&mut mv, &mut bb, &mut ground_y, &t));
+            HumanoidMovement::update_drop_through(&mv, &mut bb, &t);
+            assert!(bb.dropping_through.is_some());
+        }
+
+        mv.position.y = 448.0 - 128.0 - 33.0;
+        HumanoidMovement::update_drop_through(&mv, &mut bb, &t);
+        assert_eq!(bb.dropping_through, None);
+    }
+
+    // a two tiles high step in column 2, the corner is at (192, 448)
+    fn step(wall_rows: usize) -> Terrain {
+        let mut rows = vec![vec![TileType::Empty; 4]; 6];
+        rows[0] = vec![TileType::Block; 4];
+        for v in 1..wall_rows + 1 {
+            rows[v][2] = TileType::Block;
+        }
+        Terrain {
+            terrain: rows,
+            position: Vector2::new(0.0, 128.0),
+            width: 4,
+            height: 6,
+            tile_size: 128.0,
+        }
+    }
+
+    fn next_to_wall(top_y: f64) -> (MovingObject, HasAABB) {
+        let mut bb = HasAABB::new(AABB::new_full(
+            Vector2::new(64.0, 128.0),
+            Vector2::new(1.0, 1.0),
+        ));
+        bb.pushes_right_wall = true;
+        let mv = MovingObject::new(Vector2::new(192.0 - 32.0, top_y - 64.0));
+        (mv, bb)
+    }
+
+    #[test]
+    fn finds_ledge_along_upper_body() {
+        let t = step(2);
+        let expected = Some(Ledge {
+            corner: Vector2::new(192.0, 448.0),
+            on_left: false,
+        });
+
+        let (mv, bb) = next_to_wall(448.0);
+        assert_eq!(HumanoidMovement::find_ledge(&mv, &bb, &t), expected);
+        let (mv, bb) = next_to_wall(500.0);
+        assert_eq!(HumanoidMovement::find_ledge(&mv, &bb, &t), expected);
+
+        // only the lower body reaches the corner
+        let (mv, bb) = next_to_wall(540.0);
+        assert_eq!(HumanoidMovement::find_ledge(&mv, &bb, &t), None);
+        // not pushing toward the wall
+        let (mv, mut bb) = next_to_wall(500.0);
+        bb.pushes_right_wall = false;
+        assert_eq!(HumanoidMovement::find_ledge(&mv, &bb, &t), None);
+    }
+
+    #[test]
+    fn needs_room_above_the_ledge() {
+        let t = step(3);
+        let (mv, bb) = next_to_wall(448.0);
+        assert_eq!(HumanoidMovement::find_ledge(&mv, &bb, &t), None);
+    }
+
+    #[test]
+    fn hangs_with_top_at_the_corner() {
+        let (_, bb) = next_to_wall(0.0);
+        let ledge = Ledge {
+            corner: Vector2::new(192.0, 448.0),
+            on_left: false,
+        };
+        assert_eq!(ledge.hang_position(&bb.aabb), Vector2::new(160.0, 384.0));
+    }
 }
@@ -0,0 +1,347 @@
+use specs::Entity;
+use std::collections::{BTreeMap, HashMap};
+
+use physics::Volume;
+
+type Cell = (i64, i64);
+
+/// Cells a body is filed under, inclusive on both ends.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CellRange {
+    min: Cell,
+    max: Cell,
+}
+
+struct Body {
+    rect: Volume,
+    cells: CellRange,
+    seen: u64,
+}
+
+/// Uniform grid broadphase kept between frames. Bodies are only refiled when their
+/// rect leaves the cells it was in, pairs and queries reuse the grid's own buffers.
+///
+/// Bodies are filed with their rect grown by `margin`, so bodies that almost touch
+/// still come out as a pair.
+pub struct SpatialGrid {
+    cell_size: f64,
+    margin: f64,
+    // ordered so pairs come out the same every run, replays depend on it
+    cells: BTreeMap<Cell, Vec<Entity>>,
+    bodies: HashMap<Entity, Body>,
+    frame: u64,
+    stale: Vec<Entity>,
+    pairs: Vec<(Entity, Volume, Entity, Volume)>,
+}
+
+impl SpatialGrid {
+    pub fn new(cell_size: f64, margin: f64) -> SpatialGrid {
+        SpatialGrid {
+            cell_size,
+            margin,
+            cells: BTreeMap::new(),
+            bodies: HashMap::new(),
+            frame: 0,
+            stale: vec![],
+            pairs: vec![],
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bodies.len()
+    }
+
+    pub fn rect(&self, e: Entity) -> Option<Volume> {
+        self.bodies.get(&e).map(|b| b.rect)
+    }
+
+    /// Files every body where it is now and drops the ones that are gone.
+    pub fn update_all<I>(&mut self, bodies: I)
+    where
+        I: IntoIterator<Item = (Entity, Volume)>,
+    {
+        self.frame += 1;
+        for (e, rect) in bodies {
+            self.update(e, rect);
+        }
+
+        let frame = self.frame;
+        let mut stale = ::std::mem::replace(&mut self.stale, vec![]);
+        stale.extend(self.bodies.iter().filter(|&(_, b)| b.seen != frame).map(|(&e, _)| e));
+        for &e in stale.iter() {
+            self.remove(e);
+        }
+        stale.clear();
+        self.stale = stale;
+    }
+
+    /// Adds the body or moves it to its new rect.
+    pub fn update(&mut self, e: Entity, rect: Volume) {
+        let cells = self.cells_of(&rect);
+        let frame = self.frame;
+
+        let old = match self.bodies.get_mut(&e) {
+            Some(body) => {
+                body.seen = frame;
+                body.rect = rect;
+                if body.cells == cells {
+                    return;
+                }
+                Some(::std::mem::replace(&mut body.cells, cells))
+            }
+            None => None,
+        };
+
+        match old {
+            Some(old) => self.unfile(e, old),
+            None => {
+                self.bodies.insert(
+                    e,
+                    Body {
+                        rect,
+                        cells,
+                        seen: frame,
+                    },
+                );
+            }
+        }
+        self.file(e, cells);
+    }
+
+    pub fn remove(&mut self, e: Entity) {
+        if let Some(body) = self.bodies.remove(&e) {
+            self.unfile(e, body.cells);
+        }
+    }
+
+    /// Every two bodies within `margin` of each other, each pair once, with their rects.
+    pub fn pairs(&mut self) -> &[(Entity, Volume, Entity, Volume)] {
+        self.pairs.clear();
+        for (&cell, list) in self.cells.iter() {
+            for (i, &a) in list.iter().enumerate() {
+                for &b in list[i + 1..].iter() {
+                    let (ra, rb) = (self.bodies[&a].rect, self.bodies[&b].rect);
+                    let ga = grow(&ra, self.margin);
+                    if !ga.intersects(&rb) {
+                        continue;
+                    }
+                    // the pair shares several cells, it's only reported from the one
+                    // holding the low corner of where the grown rects meet
+                    let gb = grow(&rb, self.margin);
+                    if self.cell_at(ga.x.max(gb.x), ga.y.max(gb.y)) == cell {
+                        self.pairs.push((a, ra, b, rb));
+                    }
+                }
+            }
+        }
+        &self.pairs
+    }
+
+    /// Calls `f` once for every body touching `rect`.
+    pub fn query<F: FnMut(Entity, Volume)>(&self, rect: Volume, mut f: F) {
+        let range = self.cells_of(&rect);
+        for x in range.min.0..range.max.0 + 1 {
+            for y in range.min.1..range.max.1 + 1 {
+                let list = match self.cells.get(&(x, y)) {
+                    Some(list) => list,
+                    None => continue,
+                };
+                for &e in list.iter() {
+                    let body = &self.bodies[&e];
+                    if !rect.intersects(&body.rect) {
+                        continue;
+                    }
+                    let grown = grow(&body.rect, self.margin);
+                    if self.cell_at(rect.x.max(grown.x), rect.y.max(grown.y)) == (x, y) {
+                        f(e, body.rect);
+                    }
+                }
+            }
+        }
+    }
+
+    fn file(&mut self, e: Entity, range: CellRange) {
+        for x in range.min.0..range.max.0 + 1 {
+            for y in range.min.1..range.max.1 + 1 {
+                self.cells.entry((x, y)).or_insert_with(Vec::new).push(e);
+            }
+        }
+    }
+
+    fn unfile(&mut self, e: Entity, range: CellRange) {
+        for x in range.min.0..range.max.0 + 1 {
+            for y in range.min.1..range.max.1 + 1 {
+                let empty = match self.cells.get_mut(&(x, y)) {
+                    Some(list) => {
+                        list.retain(|&o| o != e);
+                        list.is_empty()
+                    }
+                    None => false,
+                };
+                // bodies roam, cells they left for good shouldn't pile up
+                if empty {
+                    self.cells.remove(&(x, y));
+                }
+            }
+        }
+    }
+
+    fn cells_of(&self, rect: &Volume) -> CellRange {
+        let g = grow(rect, self.margin);
+        CellRange {
+            min: self.cell_at(g.x, g.y),
+            max: self.cell_at(g.x + g.w, g.y + g.h),
+        }
+    }
+
+    fn cell_at(&self, x: f64, y: f64) -> Cell {
+        (
+            (x / self.cell_size).floor() as i64,
+            (y / self.cell_size).floor() as i64,
+        )
+    }
+}
+
+fn grow(rect: &Volume, by: f64) -> Volume {
+    Volume::new(rect.x - by, rect.y - by, rect.w + by * 2.0, rect.h + by * 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use physics::QuadTree;
+    use rand::{Rng, SeedableRng, XorShiftRng};
+    use specs::World;
+    use std::collections::HashSet;
+    use std::time::Instant;
+
+    fn scattered(world: &mut World, rng: &mut XorShiftRng, n: usize) -> Vec<(Entity, Volume)> {
+        (0..n)
+            .map(|_| {
+                let (x, y) = (rng.gen_range(0.0, 4000.0), rng.gen_range(0.0, 2000.0));
+                (world.create_entity().build(), Volume::new(x, y, 60.0, 120.0))
+            })
+            .collect()
+    }
+
+    fn brute_force(bodies: &[(Entity, Volume)], margin: f64) -> HashSet<(Entity, Entity)> {
+        let mut pairs = HashSet::new();
+        for (i, &(a, ra)) in bodies.iter().enumerate() {
+            for &(b, rb) in bodies[i + 1..].iter() {
+                if grow(&ra, margin).intersects(&rb) {
+                    pairs.insert(key(a, b));
+                }
+            }
+        }
+        pairs
+    }
+
+    fn key(a: Entity, b: Entity) -> (Entity, Entity) {
+        if a.id() < b.id() { (a, b) } else { (b, a) }
+    }
+
+    fn grid_pairs(grid: &mut SpatialGrid) -> HashSet<(Entity, Entity)> {
+        let pairs = grid.pairs();
+        let set = pairs.iter().map(|&(a, _, b, _)| key(a, b)).collect::<HashSet<_>>();
+        assert_eq!(set.len(), pairs.len(), "pair reported twice");
+        set
+    }
+
+    #[test]
+    fn finds_the_same_pairs_as_brute_force() {
+        let mut world = World::new();
+        let mut rng = XorShiftRng::from_seed([1, 2, 3, 4]);
+        let mut bodies = scattered(&mut world, &mut rng, 300);
+        let mut grid = SpatialGrid::new(128.0, 1.0);
+
+        for _ in 0..10 {
+            grid.update_all(bodies.iter().cloned());
+            assert_eq!(grid_pairs(&mut grid), brute_force(&bodies, 1.0));
+
+            for &mut (_, ref mut rect) in bodies.iter_mut() {
+                rect.x += rng.gen_range(-40.0, 40.0);
+                rect.y += rng.gen_range(-40.0, 40.0);
+            }
+            let keep = bodies.len() - rng.gen_range(0, 5);
+            bodies.truncate(keep);
+        }
+        grid.update_all(bodies.iter().cloned());
+        assert_eq!(grid.len(), bodies.len());
+    }
+
+    #[test]
+    fn drops_cells_nobody_is_in() {
+        let mut world = World::new();
+        let e = world.create_entity().build();
+        let mut grid = SpatialGrid::new(128.0, 1.0);
+
+        for i in 0..100 {
+            grid.update_all(vec![(e, Volume::new(i as f64 * 200.0, 0.0, 60.0, 120.0))]);
+        }
+        assert!(grid.cells.len() <= 4, "{} cells left", grid.cells.len());
+
+        grid.update_all(vec![]);
+        assert!(grid.cells.is_empty());
+    }
+
+    #[test]
+    fn queries_each_body_once() {
+        let mut world = World::new();
+        let mut rng = XorShiftRng::from_seed([4, 3, 2, 1]);
+        let bodies = scattered(&mut world, &mut rng, 200);
+        let mut grid = SpatialGrid::new(128.0, 1.0);
+        grid.update_all(bodies.iter().cloned());
+
+        let area = Volume::new(1000.0, 500.0, 700.0, 300.0);
+        let mut found = vec![];
+        grid.query(area, |e, _| found.push(e));
+        let expected = bodies.iter().filter(|&&(_, r)| area.intersects(&r)).count();
+        assert_eq!(found.len(), expected);
+        assert_eq!(found.iter().collect::<HashSet<_>>().len(), expected);
+    }
+
+    #[test]
+    fn beats_rebuilding_a_quad_tree_every_frame() {
+        let mut world = World::new();
+        let mut rng = XorShiftRng::from_seed([9, 9, 9, 9]);
+        let bodies = scattered(&mut world, &mut rng, 1000);
+        let bounds = Volume::new(0.0, 0.0, 4000.0, 2000.0);
+        let frames = 20;
+
+        let start = Instant::now();
+        let mut found = 0;
+        for _ in 0..frames {
+            let mut qt = QuadTree::new(bounds);
+            for &(e, rect) in bodies.iter() {
+                qt.insert(e, rect);
+            }
+            for &(_, rect) in bodies.iter() {
+                found += qt.retrieve(rect).len();
+            }
+        }
+        let quad_tree = start.elapsed();
+        assert!(found > 0);
+
+        // everything moving, the worst case for the grid
+        let mut grid = SpatialGrid::new(128.0, 1.0);
+        let mut moving = bodies.clone();
+        let start = Instant::now();
+        let mut found = 0;
+        for frame in 0..frames {
+            for &mut (_, ref mut rect) in moving.iter_mut() {
+                rect.x += if frame % 2 == 0 { 5.0 } else { -5.0 };
+            }
+            grid.update_all(moving.iter().cloned());
+            found += grid.pairs().len();
+        }
+        let grid_time = start.elapsed();
+        assert!(found > 0);
+
+        assert!(
+            grid_time < quad_tree,
+            "grid took {:?}, the quad tree {:?}",
+            grid_time,
+            quad_tree
+        );
+    }
+}
@@ -3,5 +3,8 @@ pub mod systems;
 
 mod quad_tree;
 mod aabb;
+mod grid;
 
 pub use self::aabb::*;
+pub use self::grid::SpatialGrid;
+pub use self::quad_tree::{Positioned, QuadTree, Volume};
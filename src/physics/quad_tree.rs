@@ -1,4 +1,5 @@
 use specs::Entity;
+use std::cmp::Ordering;
 
 use physics::components::*;
 use util::Vector2;
@@ -7,20 +8,99 @@ pub trait Positioned {
     fn to_rect(&self) -> Volume;
 }
 
+/// Spatial index over entity rects, rebuilt or updated by whoever owns it.
+/// Rects that don't fit in the bounds go to an overflow bucket that every query
+/// also looks through.
 pub struct QuadTree {
+    root: Node,
+    outside: Vec<(Entity, Volume)>,
+}
+
+struct Node {
     level: usize,
     bounds: Volume,
     objects: Vec<(Entity, Volume)>,
-    nodes: Option<Box<[QuadTree; 4]>>,
+    nodes: Option<Box<[Node; 4]>>,
 }
 
 impl QuadTree {
     pub fn new(bounds: Volume) -> QuadTree {
-        QuadTree::create(0, bounds)
+        QuadTree {
+            root: Node::new(0, bounds),
+            outside: vec![],
+        }
     }
 
-    fn create(level: usize, bounds: Volume) -> QuadTree {
-        QuadTree {
+    pub fn bounds(&self) -> Volume {
+        self.root.bounds
+    }
+
+    pub fn clear(&mut self) {
+        self.root = Node::new(0, self.root.bounds);
+        self.outside.clear();
+    }
+
+    pub fn insert(&mut self, entity: Entity, rect: Volume) {
+        if self.root.bounds.contains(&rect) {
+            self.root.insert(entity, rect);
+        } else {
+            self.outside.push((entity, rect));
+        }
+    }
+
+    /// Takes the entity out wherever it was put, returns whether it was there.
+    pub fn remove(&mut self, entity: Entity) -> bool {
+        let before = self.outside.len();
+        self.outside.retain(|&(e, _)| e != entity);
+        self.root.remove(entity) || self.outside.len() != before
+    }
+
+    /// Everything that might touch `rect`, a superset to check the exact shapes against.
+    pub fn retrieve(&self, rect: Volume) -> Vec<(Entity, Volume)> {
+        let mut ret = self.outside.clone();
+        self.root.retrieve(&mut ret, &rect);
+        ret
+    }
+
+    /// Rects touching `rect`.
+    pub fn query_rect(&self, rect: Volume) -> Vec<(Entity, Volume)> {
+        let mut ret = self.retrieve(rect);
+        ret.retain(|&(_, v)| v.intersects(&rect));
+        ret
+    }
+
+    /// Rects the point is in, edges included.
+    pub fn query_point(&self, point: Vector2) -> Vec<(Entity, Volume)> {
+        let mut ret = self.retrieve(Volume::new(point.x, point.y, 0.0, 0.0));
+        ret.retain(|&(_, v)| v.contains_point(point));
+        ret
+    }
+
+    /// First rect along the segment, with how far along it was hit, 0.0 at `from`
+    /// and 1.0 at `to`.
+    pub fn raycast(&self, from: Vector2, to: Vector2) -> Option<(Entity, Volume, f64)> {
+        let mut best = None;
+        for &(e, v) in self.outside.iter() {
+            closer_hit(&mut best, e, v, from, to);
+        }
+        self.root.raycast(&mut best, from, to);
+        best
+    }
+
+    /// The rect closest to the point, inside it counting as no distance at all.
+    pub fn nearest(&self, point: Vector2) -> Option<(Entity, Volume)> {
+        let mut best = None;
+        for &(e, v) in self.outside.iter() {
+            closer(&mut best, e, v, point);
+        }
+        self.root.nearest(&mut best, point);
+        best.map(|(e, v, _)| (e, v))
+    }
+}
+
+impl Node {
+    fn new(level: usize, bounds: Volume) -> Node {
+        Node {
             level,
             bounds,
             objects: vec![],
@@ -28,10 +108,6 @@ impl QuadTree {
         }
     }
 
-    // pub fn clear(&mut self) {
-    //     self.objects.clear();
-    // }
-
     fn split(&mut self) {
         let sub_width = self.bounds.w / 2.0;
         let sub_height = self.bounds.h / 2.0;
@@ -43,102 +119,125 @@ impl QuadTree {
 
         self.nodes = Some(Box::new(
             [
-                QuadTree::create(
+                Node::new(level, Volume::new(x + sub_width, y, sub_width, sub_height)),
+                Node::new(level, Volume::new(x, y, sub_width, sub_height)),
+                Node::new(level, Volume::new(x, y + sub_height, sub_width, sub_height)),
+                Node::new(
                     level,
-                    Volume::new(x + sub_width, y, sub_width, sub_height),
-                ),
-                QuadTree::create(
-                    level,
-                    Volume::new(x, y, sub_width, sub_height),
-                ),
-                QuadTree::create(
-                    level,
-                    Volume::new(x, y + sub_height, sub_width, sub_height),
-                ),
-                QuadTree::create(
-                    level,
-                    Volume::new(
-                        x + sub_width,
-                        y + sub_height,
-                        sub_width,
-                        sub_height,
-                    ),
-                ),
-            ],
-        ));
-        self.nodes = Some(Box::new(
-            [
-                QuadTree::create(
-                    level,
-                    Volume::new(x + sub_width, y, sub_width, sub_height),
-                ),
-                QuadTree::create(
-                    level,
-                    Volume::new(x, y, sub_width, sub_height),
-                ),
-                QuadTree::create(
-                    level,
-                    Volume::new(x, y + sub_height, sub_width, sub_height),
-                ),
-                QuadTree::create(
-                    level,
-                    Volume::new(
-                        x + sub_width,
-                        y + sub_height,
-                        sub_width,
-                        sub_height,
-                    ),
+                    Volume::new(x + sub_width, y + sub_height, sub_width, sub_height),
                 ),
             ],
         ));
     }
 
-    pub fn insert(&mut self, entity: Entity, rect: Volume) {
+    fn insert(&mut self, entity: Entity, rect: Volume) {
         if let Some(ref mut nodes) = self.nodes {
             let index = get_index(&self.bounds, &rect);
             if index != -1 {
-                (*nodes)[index as usize].insert(entity, rect.clone());
+                nodes[index as usize].insert(entity, rect);
                 return;
             }
         }
 
         self.objects.push((entity, rect));
 
-        if self.objects.len() > MAX_OBJECTS && self.level < MAX_LEVELS {
-            if self.nodes.is_none() {
-                self.split();
+        // once split, whatever stays here straddles the children
+        if self.nodes.is_none() && self.objects.len() > MAX_OBJECTS && self.level < MAX_LEVELS {
+            self.split();
+
+            let objects = ::std::mem::replace(&mut self.objects, vec![]);
+            for (e, rect) in objects {
+                self.insert(e, rect);
+            }
+        }
+    }
+
+    fn remove(&mut self, entity: Entity) -> bool {
+        let before = self.objects.len();
+        self.objects.retain(|&(e, _)| e != entity);
+        let mut removed = self.objects.len() != before;
+        if let Some(ref mut nodes) = self.nodes {
+            for node in nodes.iter_mut() {
+                removed = node.remove(entity) || removed;
             }
+        }
+        removed
+    }
 
-            for o in self.objects.drain(..) {
-                let ix = get_index(&self.bounds, &o.1);
+    fn retrieve(&self, ret: &mut Vec<(Entity, Volume)>, rect: &Volume) {
+        ret.extend(self.objects.iter().cloned());
+        if let Some(ref nodes) = self.nodes {
+            for node in nodes.iter().filter(|n| n.bounds.intersects(rect)) {
+                node.retrieve(ret, rect);
+            }
+        }
+    }
 
-                if ix != -1 {
-                    if let Some(ref mut nodes) = self.nodes {
-                        (*nodes)[ix as usize].insert(entity, rect);
-                    }
-                }
+    fn raycast(&self, best: &mut Option<(Entity, Volume, f64)>, from: Vector2, to: Vector2) {
+        let reach = match self.bounds.ray_hit(from, to) {
+            Some(t) => t,
+            None => return,
+        };
+        if let Some((_, _, t)) = *best {
+            if reach > t {
+                return;
+            }
+        }
+
+        for &(e, v) in self.objects.iter() {
+            closer_hit(best, e, v, from, to);
+        }
+        if let Some(ref nodes) = self.nodes {
+            for node in nodes.iter() {
+                node.raycast(best, from, to);
             }
         }
     }
 
-    fn retrieve_rec(&self, ret: &mut Vec<(Entity, Volume)>, rect: Volume) {
-        let ix = get_index(&self.bounds, &rect);
-        if ix != -1 {
-            if let Some(ref nodes) = self.nodes {
-                (*nodes)[ix as usize].retrieve_rec(ret, rect);
+    fn nearest(&self, best: &mut Option<(Entity, Volume, f64)>, point: Vector2) {
+        if let Some((_, _, d)) = *best {
+            if self.bounds.distance_to(point) > d {
+                return;
             }
         }
 
-        let owned_iter =
-            self.objects.iter().map(|&(e, v)| (e.clone(), v.clone())).collect::<Vec<_>>();
+        for &(e, v) in self.objects.iter() {
+            closer(best, e, v, point);
+        }
+        if let Some(ref nodes) = self.nodes {
+            let mut order = [0, 1, 2, 3];
+            order.sort_by(|&a, &b| {
+                let (da, db) = (
+                    nodes[a].bounds.distance_to(point),
+                    nodes[b].bounds.distance_to(point),
+                );
+                da.partial_cmp(&db).unwrap_or(Ordering::Equal)
+            });
+            for &i in order.iter() {
+                nodes[i].nearest(best, point);
+            }
+        }
+    }
+}
 
-        ret.extend(owned_iter);
+fn closer_hit(
+    best: &mut Option<(Entity, Volume, f64)>,
+    e: Entity,
+    v: Volume,
+    from: Vector2,
+    to: Vector2,
+) {
+    if let Some(t) = v.ray_hit(from, to) {
+        if best.map_or(true, |(_, _, b)| t < b) {
+            *best = Some((e, v, t));
+        }
     }
+}
 
-    pub fn retrieve(&self, rect: Volume) -> Vec<(Entity, Volume)> {
-        let mut ret = vec![];
-        self.retrieve_rec(&mut ret, rect);
-        ret
+fn closer(best: &mut Option<(Entity, Volume, f64)>, e: Entity, v: Volume, point: Vector2) {
+    let d = v.distance_to(point);
+    if best.map_or(true, |(_, _, b)| d < b) {
+        *best = Some((e, v, d));
     }
 }
 
@@ -160,9 +259,77 @@ impl Volume {
               other.y + other.h < self.y)
     }
 
+    /// Whether `other` is entirely inside, edges included.
+    pub fn contains(&self, other: &Volume) -> bool {
+        other.x >= self.x && other.y >= self.y && other.x + other.w <= self.x + self.w &&
+            other.y + other.h <= self.y + self.h
+    }
+
+    pub fn contains_point(&self, p: Vector2) -> bool {
+        p.x >= self.x && p.x <= self.x + self.w && p.y >= self.y && p.y <= self.y + self.h
+    }
+
+    /// How far the point is from the closest edge, zero inside.
+    pub fn distance_to(&self, p: Vector2) -> f64 {
+        let dx = (self.x - p.x).max(p.x - (self.x + self.w)).max(0.0);
+        let dy = (self.y - p.y).max(p.y - (self.y + self.h)).max(0.0);
+        (dx * dx + dy * dy).sqrt()
+    }
+
+    /// Where the segment enters the volume, 0.0 at `from` and 1.0 at `to`. A segment
+    /// starting inside hits right away.
+    pub fn ray_hit(&self, from: Vector2, to: Vector2) -> Option<f64> {
+        let d = to - from;
+        let (mut enter, mut exit) = (0.0f64, 1.0f64);
+        for &(start, delta, min, max) in &[
+            (from.x, d.x, self.x, self.x + self.w),
+            (from.y, d.y, self.y, self.y + self.h),
+        ]
+        {
+            if delta == 0.0 {
+                if start < min || start > max {
+                    return None;
+                }
+                continue;
+            }
+            let (t0, t1) = ((min - start) / delta, (max - start) / delta);
+            enter = enter.max(t0.min(t1));
+            exit = exit.min(t0.max(t1));
+        }
+        if enter <= exit { Some(enter) } else { None }
+    }
+
     pub fn center(&self) -> Vector2 {
         Vector2::new(self.x + self.w / 2.0, self.y + self.h / 2.0)
     }
+
+    fn overlap(&self, other: &Volume) -> (f64, f64) {
+        (
+            (self.x + self.w).min(other.x + other.w) - self.x.max(other.x),
+            (self.y + self.h).min(other.y + other.h) - self.y.max(other.y),
+        )
+    }
+
+    /// Shortest move that takes `self` out of `other`, none unless they overlap.
+    pub fn separation(&self, other: &Volume) -> Option<Vector2> {
+        let (dx, dy) = self.overlap(other);
+        if dx <= 0.0 || dy <= 0.0 {
+            return None;
+        }
+
+        let (c, o) = (self.center(), other.center());
+        if dx < dy {
+            Some(Vector2::new(if c.x < o.x { -dx } else { dx }, 0.0))
+        } else {
+            Some(Vector2::new(0.0, if c.y < o.y { -dy } else { dy }))
+        }
+    }
+
+    /// Whether the bottom of `self` is on the top of `other`, give or take `skin`.
+    pub fn rests_on(&self, other: &Volume, skin: f64) -> bool {
+        let (dx, _) = self.overlap(other);
+        dx > 0.0 && (self.y - (other.y + other.h)).abs() <= skin
+    }
 }
 
 impl<'a> Positioned for (&'a MovingObject, &'a HasAABB) {
@@ -202,3 +369,111 @@ fn get_index(bounds: &Volume, rect: &Volume) -> isize {
 
 const MAX_OBJECTS: usize = 10;
 const MAX_LEVELS: usize = 5;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, XorShiftRng};
+    use specs::World;
+    use std::collections::HashSet;
+
+    // some of them stick out of or lie past the 1000 x 1000 bounds
+    fn random_rect(rng: &mut XorShiftRng) -> Volume {
+        Volume::new(
+            rng.gen_range(-200.0, 1200.0),
+            rng.gen_range(-200.0, 1200.0),
+            rng.gen_range(1.0, 80.0),
+            rng.gen_range(1.0, 80.0),
+        )
+    }
+
+    fn random_point(rng: &mut XorShiftRng) -> Vector2 {
+        Vector2::new(rng.gen_range(-250.0, 1250.0), rng.gen_range(-250.0, 1250.0))
+    }
+
+    fn entities(found: Vec<(Entity, Volume)>) -> HashSet<Entity> {
+        found.into_iter().map(|(e, _)| e).collect()
+    }
+
+    fn brute_force<F: Fn(&Volume) -> bool>(all: &[(Entity, Volume)], f: F) -> HashSet<Entity> {
+        all.iter().filter(|&&(_, v)| f(&v)).map(|&(e, _)| e).collect()
+    }
+
+    fn min_by<F: Fn(&Volume) -> Option<f64>>(all: &[(Entity, Volume)], f: F) -> Option<f64> {
+        all.iter().filter_map(|&(_, v)| f(&v)).fold(None, |best, d| match best {
+            Some(b) if b <= d => Some(b),
+            _ => Some(d),
+        })
+    }
+
+    #[test]
+    fn queries_agree_with_brute_force() {
+        for seed in 1..20 {
+            let mut rng = XorShiftRng::from_seed([seed, 7, 13, 42]);
+            let mut world = World::new();
+            let mut qt = QuadTree::new(Volume::new(0.0, 0.0, 1000.0, 1000.0));
+            let mut all = (0..rng.gen_range(0, 300))
+                .map(|_| (world.create_entity().build(), random_rect(&mut rng)))
+                .collect::<Vec<_>>();
+            for &(e, v) in all.iter() {
+                qt.insert(e, v);
+            }
+
+            for round in 0..2 {
+                for _ in 0..30 {
+                    let area = random_rect(&mut rng);
+                    assert_eq!(
+                        entities(qt.query_rect(area)),
+                        brute_force(&all, |v| v.intersects(&area))
+                    );
+
+                    let p = random_point(&mut rng);
+                    assert_eq!(
+                        entities(qt.query_point(p)),
+                        brute_force(&all, |v| v.contains_point(p))
+                    );
+
+                    let to = random_point(&mut rng);
+                    assert_eq!(
+                        qt.raycast(p, to).map(|(_, _, t)| t),
+                        min_by(&all, |v| v.ray_hit(p, to))
+                    );
+                    assert_eq!(
+                        qt.nearest(p).map(|(_, v)| v.distance_to(p)),
+                        min_by(&all, |v| Some(v.distance_to(p)))
+                    );
+                }
+
+                if round == 0 {
+                    let keep = all.len() / 2;
+                    for &(e, _) in all[keep..].iter() {
+                        assert!(qt.remove(e));
+                    }
+                    all.truncate(keep);
+                }
+            }
+
+            qt.clear();
+            assert!(qt.query_rect(Volume::new(-500.0, -500.0, 2000.0, 2000.0)).is_empty());
+        }
+    }
+
+    #[test]
+    fn keeps_everything_through_splits() {
+        let mut world = World::new();
+        let mut qt = QuadTree::new(Volume::new(0.0, 0.0, 1000.0, 1000.0));
+        // the first one straddles the middle and stays at the root
+        let mut all = vec![(world.create_entity().build(), Volume::new(490.0, 490.0, 20.0, 20.0))];
+        for i in 0..40 {
+            let v = Volume::new(10.0 + i as f64 * 5.0, 10.0, 2.0, 2.0);
+            all.push((world.create_entity().build(), v));
+        }
+        for &(e, v) in all.iter() {
+            qt.insert(e, v);
+        }
+
+        let found = qt.query_rect(qt.bounds());
+        assert_eq!(found.len(), all.len());
+        assert_eq!(entities(found), brute_force(&all, |_| true));
+    }
+}
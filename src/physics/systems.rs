@@ -1,8 +1,10 @@
 use components::Position;
+use physics::{SpatialGrid, Volume};
 use physics::components::*;
 use rayon::iter::ParallelIterator;
 use resources::*;
 use specs::*;
+use std::collections::{HashMap, HashSet};
 use util::*;
 
 // pub struct MovingSystem;
@@ -49,18 +51,35 @@ impl<'a> System<'a> for AABBMovingSystem {
             bb.pushed_right_wall = bb.pushes_right_wall;
 
             let mut ground_y = 0.0;
+            let mut slope_y = 0.0;
             let mut ceiling_y = 0.0;
             let mut right_wall_x = 0.0;
             let mut left_wall_x = 0.0;
 
             bb.on_platform = false;
+            let on_entity = bb.on_entity;
+            bb.on_entity = false;
 
-            if mv.velocity.y <= 0.0 &&
-                HumanoidMovement::has_ground(mv, bb, &mut ground_y, terrain)
-            {
+            bb.on_slope = mv.velocity.y <= 0.0 &&
+                HumanoidMovement::has_slope_ground(mv, bb, &mut slope_y, terrain);
+
+            let has_ground = mv.velocity.y <= 0.0 &&
+                HumanoidMovement::has_ground(mv, bb, &mut ground_y, terrain);
+
+            if has_ground || bb.on_slope {
+                if bb.on_slope {
+                    ground_y = if has_ground { ground_y.max(slope_y) } else { slope_y };
+                }
                 mv.position.y = ground_y + bb.aabb.half_size.y - bb.aabb.offset.y;
                 mv.velocity.y = 0.0;
                 bb.on_ground = true;
+                bb.grounded_at = time.now;
+            } else if on_entity && mv.velocity.y <= 0.0 {
+                // the body underneath is checked again in `CollisionSystem`
+                mv.position.y = mv.old_position.y;
+                mv.velocity.y = 0.0;
+                bb.on_ground = true;
+                bb.grounded_at = time.now;
             } else {
                 bb.on_ground = false;
             }
@@ -101,86 +120,393 @@ impl<'a> System<'a> for AABBMovingSystem {
             } else {
                 bb.at_ceiling = false;
             }
+
+            HumanoidMovement::update_drop_through(mv, bb, terrain);
+            HumanoidMovement::update_surroundings(mv, bb, terrain);
+            bb.ledge = HumanoidMovement::find_ledge(mv, bb, terrain);
         });
     }
 }
 
+/// Bodies closer than this count as resting on each other.
+pub const CONTACT_SKIN: f64 = 1.0;
+
+/// Pushes overlapping bodies apart the shortest way out, split between them by mass.
+/// A body pushed up or resting on top of another stands on it like on ground.
+/// `CollisionLayers` decides which pairs are resolved, which are only reported in
+/// `CollisionEvents` and which are left alone. Candidate pairs come from the
+/// `SpatialGrid`, which only refiles bodies that moved to other cells, pushed bodies
+/// are refiled again after the push.
 pub struct CollisionSystem;
 impl<'a> System<'a> for CollisionSystem {
     type SystemData = (Entities<'a>,
      WriteStorage<'a, MovingObject>,
-     ReadStorage<'a, HasAABB>,
+     WriteStorage<'a, HasAABB>,
      ReadStorage<'a, CollisionDetection>,
-     Fetch<'a, LevelTerrain>,
-     Fetch<'a, DeltaTime>);
+     Fetch<'a, DeltaTime>,
+     Fetch<'a, RandomSeed>,
+     Fetch<'a, CollisionLayers>,
+     FetchMut<'a, SpatialGrid>,
+     FetchMut<'a, CollisionEvents>);
 
     fn run(&mut self, data: Self::SystemData) {
         use physics::quad_tree::*;
-        use rand;
+        use rand::Rng;
 
-        let (e, mut mv, bb, cd, t, time) = data;
+        let (e, mut mv, mut bb, cd, time, seed, layers, mut grid, mut events) = data;
+        events.events.clear();
 
-        let terrain_rect = {
-            let x = t.terrain.position.x;
-            let y = t.terrain.position.y;
-            let w = t.terrain.width as f64 * t.terrain.tile_size;
-            let h = t.terrain.height as f64 * t.terrain.tile_size;
-            Volume::new(x, y, w, h)
-        };
+        grid.update_all(
+            (&*e, &mv, &bb, &cd)
+                .join()
+                .map(|(e, mv, bb, _)| (e, (mv, bb).to_rect())),
+        );
+
+        let mut pushes: HashMap<Entity, Vector2> = HashMap::new();
+        let mut resting = HashSet::new();
+
+        for &(a, rect_a, b, rect_b) in grid.pairs() {
+            let (cd_a, cd_b) = match (cd.get(a), cd.get(b)) {
+                (Some(cd_a), Some(cd_b)) => (cd_a, cd_b),
+                _ => continue,
+            };
 
-        let mut qt = QuadTree::new(terrain_rect);
+            match layers.response(cd_a.layer, cd_b.layer) {
+                Response::Ignore => continue,
+                Response::Report => {
+                    if let Some(mtv) = rect_a.separation(&rect_b) {
+                        events.events.push(Collision {
+                            a,
+                            b,
+                            normal: mtv.normalize(),
+                            depth: mtv.norm(),
+                        });
+                    }
+                    continue;
+                }
+                Response::Resolve => (),
+            }
 
-        {
-            for (e, mv, bb, _) in (&*e, &mv, &bb, &cd).join() {
-                let rect = (mv, bb).to_rect();
-                qt.insert(e.clone(), rect);
+            if let Some(mut mtv) = rect_a.separation(&rect_b) {
+                // stacked exactly on top of each other, either way out will do
+                if rect_a.center() == rect_b.center() && seed.rng(a.id(), time.now).gen() {
+                    mtv = -mtv;
+                }
+                let (share_a, share_b) = shares(cd_a.mass, cd_b.mass);
+                *pushes.entry(a).or_insert(Vector2::new(0.0, 0.0)) += mtv * share_a;
+                *pushes.entry(b).or_insert(Vector2::new(0.0, 0.0)) -= mtv * share_b;
+                events.events.push(Collision {
+                    a,
+                    b,
+                    normal: mtv.normalize(),
+                    depth: mtv.norm(),
+                });
+            } else if let Some((top, bottom)) = resting_pair(a, rect_a, b, rect_b) {
+                resting.insert(top);
+                events.events.push(Collision {
+                    a: top,
+                    b: bottom,
+                    normal: Vector2::new(0.0, 1.0),
+                    depth: 0.0,
+                });
             }
         }
 
-        let qt = qt;
+        // in a fixed order, the grid's cell lists and so the pairs depend on it
+        let mut pushes = pushes.into_iter().collect::<Vec<_>>();
+        pushes.sort_by_key(|&(e, _)| e.id());
 
-        (&*e, &mut mv, &bb, &cd).par_join().for_each(|(e, mv, bb, _)| {
-            let vol = (&*mv, bb).to_rect();
+        for (e, push) in pushes {
+            if let Some(mv) = mv.get_mut(e) {
+                mv.position += push;
+                if push.x > 0.0 {
+                    mv.velocity.x = mv.velocity.x.max(0.0);
+                } else if push.x < 0.0 {
+                    mv.velocity.x = mv.velocity.x.min(0.0);
+                }
+                if push.y > 0.0 {
+                    resting.insert(e);
+                } else if push.y < 0.0 {
+                    mv.velocity.y = mv.velocity.y.min(0.0);
+                }
+            }
+            if let (Some(mv), Some(bb)) = (mv.get(e), bb.get(e)) {
+                grid.update(e, (mv, bb).to_rect());
+            }
+        }
 
-            let iter = qt.retrieve(vol);
+        for e in resting {
+            if let (Some(mv), Some(bb)) = (mv.get_mut(e), bb.get_mut(e)) {
+                mv.velocity.y = mv.velocity.y.max(0.0);
+                bb.on_entity = true;
+                bb.on_ground = true;
+                bb.grounded_at = time.now;
+            }
+        }
+    }
+}
 
-            for (ce, cv) in iter {
-                if vol.intersects(&cv) && e != ce {
-                    let center = mv.position + bb.aabb.offset;
-                    let o_center = cv.center();
+/// How much of a push each of two bodies takes.
+fn shares(mass_a: f64, mass_b: f64) -> (f64, f64) {
+    match (mass_a.is_infinite(), mass_b.is_infinite()) {
+        (true, true) => (0.0, 0.0),
+        (true, false) => (0.0, 1.0),
+        (false, true) => (1.0, 0.0),
+        (false, false) => (mass_b / (mass_a + mass_b), mass_a / (mass_a + mass_b)),
+    }
+}
 
-                    let dir = (center - o_center).normalize();
-                    // println!("Direction: {:?}", dir);
-                    // println!("New velo: {:?}", mv.velocity - dir * time.delta);
-                    let force = 10000.0 * time.delta;
+/// Which of the two sits on the other, top first.
+fn resting_pair(
+    a: Entity,
+    rect_a: Volume,
+    b: Entity,
+    rect_b: Volume,
+) -> Option<(Entity, Entity)> {
+    if rect_a.rests_on(&rect_b, CONTACT_SKIN) {
+        Some((a, b))
+    } else if rect_b.rests_on(&rect_a, CONTACT_SKIN) {
+        Some((b, a))
+    } else {
+        None
+    }
+}
 
-                    let velo_delta = dir * force;
-                    if (velo_delta.x.is_nan() || velo_delta.x.abs() > 50000.0) &&
-                        (velo_delta.y.is_nan() || velo_delta.y.abs() > 50000.0)
-                    {
-                        mv.velocity += Vector2::new(
-                            rand::random::<f64>() - 0.5,
-                            rand::random::<f64>() - 0.5,
-                        ).normalize() * force;
-                    } else {
-                        mv.velocity += velo_delta;
-                    }
+/// Tracks which bodies are inside each `Trigger` and reports the changes in
+/// `TriggerEvents`, through the same layers as `CollisionSystem` and the grid it
+/// left up to date.
+pub struct TriggerSystem;
+impl<'a> System<'a> for TriggerSystem {
+    type SystemData = (Entities<'a>,
+     ReadStorage<'a, CollisionDetection>,
+     WriteStorage<'a, Trigger>,
+     Fetch<'a, CollisionLayers>,
+     Fetch<'a, SpatialGrid>,
+     FetchMut<'a, TriggerEvents>);
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (e, cd, mut triggers, layers, grid, mut events) = data;
+        events.events.clear();
+
+        let mut inside = vec![];
+        for (te, trigger) in (&*e, &mut triggers).join() {
+            inside.clear();
+            grid.query(trigger.volume, |b, rect| {
+                let layer = match cd.get(b) {
+                    Some(cd) => cd.layer,
+                    None => return,
+                };
+                if layers.response(trigger.layer, layer) != Response::Ignore &&
+                    trigger.volume.separation(&rect).is_some()
+                {
+                    inside.push(b);
                 }
+            });
+
+            for &b in inside.iter() {
+                events.events.push(TriggerEvent {
+                    trigger: te,
+                    kind: trigger.kind,
+                    other: b,
+                    overlap: if trigger.inside.contains(&b) {
+                        Overlap::Stay
+                    } else {
+                        Overlap::Enter
+                    },
+                });
+            }
+            for &b in trigger.inside.iter().filter(|b| !inside.contains(b)) {
+                events.events.push(TriggerEvent {
+                    trigger: te,
+                    kind: trigger.kind,
+                    other: b,
+                    overlap: Overlap::Exit,
+                });
             }
-        })
+            ::std::mem::swap(&mut trigger.inside, &mut inside);
+        }
     }
 }
 
-pub struct PositionSystem;
+/// Places sprites between the last two simulation steps, `alpha` of the way to the latest.
+pub struct PositionSystem {
+    pub alpha: f64,
+}
 
 impl<'a> System<'a> for PositionSystem {
     type SystemData = (ReadStorage<'a, MovingObject>, WriteStorage<'a, Position>);
 
     fn run(&mut self, data: Self::SystemData) {
         let (mv, mut pos) = data;
+        let alpha = self.alpha;
         (&mv, &mut pos).par_join().for_each(|(mv, pos)| {
-            pos.x = mv.position.x as f32;
-            pos.y = mv.position.y as f32;
+            let p = lerp(&mv.old_position, &mv.position, alpha);
+            pos.x = p.x as f32;
+            pos.y = p.y as f32;
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use components::register_components;
+    use level::{Terrain, TileType};
+    use physics::AABB;
+
+    fn bodies(at: &[(f64, f64, f64)]) -> (World, Vec<Entity>) {
+        let mut world = World::new();
+        register_components(&mut world);
+        world.add_resource(LevelTerrain {
+            terrain: Terrain {
+                terrain: vec![vec![TileType::Empty; 8]; 8],
+                position: Vector2::new(0.0, 128.0),
+                width: 8,
+                height: 8,
+                tile_size: 128.0,
+            },
+            spawn: Vector2::new(0.0, 0.0),
         });
+        world.add_resource(DeltaTime::new());
+        world.add_resource(RandomSeed(1));
+        world.add_resource(CollisionEvents::new());
+        world.add_resource(TriggerEvents::new());
+        world.add_resource(SpatialGrid::new(128.0, CONTACT_SKIN));
+
+        let mut layers = CollisionLayers::new(2);
+        layers.set(0, 0, Response::Resolve);
+        layers.set(0, 1, Response::Report);
+        world.add_resource(layers);
+
+        let entities = at.iter()
+            .map(|&(x, y, mass)| {
+                world
+                    .create_entity()
+                    .with(MovingObject::new(Vector2::new(x, y)))
+                    .with(HasAABB::new(AABB::new_full(
+                        Vector2::new(100.0, 100.0),
+                        Vector2::new(1.0, 1.0),
+                    )))
+                    .with(CollisionDetection { layer: 0, mass })
+                    .build()
+            })
+            .collect();
+        (world, entities)
+    }
+
+    fn position(world: &World, e: Entity) -> Vector2 {
+        world.read::<MovingObject>().get(e).unwrap().position
+    }
+
+    #[test]
+    fn overlapping_bodies_split_the_push_by_mass() {
+        let (mut world, e) = bodies(&[(500.0, 500.0, 1.0), (580.0, 510.0, 1.0)]);
+        CollisionSystem.run_now(&mut world.res);
+        assert_eq!(position(&world, e[0]), Vector2::new(490.0, 500.0));
+        assert_eq!(position(&world, e[1]), Vector2::new(590.0, 510.0));
+
+        let events = world.read_resource::<CollisionEvents>();
+        assert_eq!(events.events.len(), 1);
+        assert_eq!(events.events[0].depth, 20.0);
+        assert_eq!(events.involving(e[1]).len(), 1);
+
+        let (mut world, e) = bodies(&[(500.0, 500.0, 3.0), (580.0, 500.0, 1.0)]);
+        CollisionSystem.run_now(&mut world.res);
+        assert_eq!(position(&world, e[0]), Vector2::new(495.0, 500.0));
+        assert_eq!(position(&world, e[1]), Vector2::new(595.0, 500.0));
+    }
+
+    #[test]
+    fn layers_decide_what_is_resolved() {
+        let (mut world, e) = bodies(&[(500.0, 500.0, 1.0), (580.0, 500.0, 1.0)]);
+        world.write::<CollisionDetection>().get_mut(e[1]).unwrap().layer = 1;
+        CollisionSystem.run_now(&mut world.res);
+        assert_eq!(position(&world, e[0]), Vector2::new(500.0, 500.0));
+        assert_eq!(world.read_resource::<CollisionEvents>().events.len(), 1);
+
+        world.write::<CollisionDetection>().get_mut(e[0]).unwrap().layer = 1;
+        CollisionSystem.run_now(&mut world.res);
+        assert!(world.read_resource::<CollisionEvents>().events.is_empty());
+    }
+
+    #[test]
+    fn triggers_report_enter_stay_and_exit() {
+        let (mut world, e) = bodies(&[(500.0, 500.0, 1.0)]);
+        let volume = Volume::new(550.0, 400.0, 100.0, 100.0);
+        let trigger = world
+            .create_entity()
+            .with(Trigger::new(TriggerKind::Kill, "pit".to_owned(), volume, 1))
+            .build();
+
+        let overlaps = |world: &mut World, x: f64| {
+            world.write::<MovingObject>().get_mut(e[0]).unwrap().position.x = x;
+            CollisionSystem.run_now(&mut world.res);
+            TriggerSystem.run_now(&mut world.res);
+            let events = world.read_resource::<TriggerEvents>().events.clone();
+            assert!(events.iter().all(|t| t.trigger == trigger && t.other == e[0]));
+            events.iter().map(|t| t.overlap).collect::<Vec<_>>()
+        };
+
+        assert_eq!(overlaps(&mut world, 500.0), vec![Overlap::Enter]);
+        assert_eq!(overlaps(&mut world, 520.0), vec![Overlap::Stay]);
+        assert_eq!(overlaps(&mut world, 900.0), vec![Overlap::Exit]);
+        assert_eq!(overlaps(&mut world, 900.0), vec![]);
+
+        // the trigger layer ignores layer 0 bodies
+        world.write::<Trigger>().get_mut(trigger).unwrap().layer = 2;
+        assert_eq!(overlaps(&mut world, 500.0), vec![]);
+    }
+
+    #[test]
+    fn triggers_see_where_bodies_were_pushed_to() {
+        let (mut world, e) = bodies(&[(500.0, 500.0, 1.0), (580.0, 500.0, 1.0)]);
+        let volume = Volume::new(635.0, 400.0, 100.0, 200.0);
+        world
+            .create_entity()
+            .with(Trigger::new(TriggerKind::Kill, "pit".to_owned(), volume, 1))
+            .build();
+
+        CollisionSystem.run_now(&mut world.res);
+        TriggerSystem.run_now(&mut world.res);
+        let events = world.read_resource::<TriggerEvents>();
+        assert_eq!(events.entered(TriggerKind::Kill).len(), 1);
+        assert_eq!(events.events[0].other, e[1]);
+    }
+
+    #[test]
+    fn stands_on_top_of_another_body() {
+        let inf = ::std::f64::INFINITY;
+        let (mut world, e) = bodies(&[(500.0, 500.0, inf), (510.0, 595.0, 1.0)]);
+        world.write::<MovingObject>().get_mut(e[1]).unwrap().velocity.y = -300.0;
+
+        CollisionSystem.run_now(&mut world.res);
+        assert_eq!(position(&world, e[0]), Vector2::new(500.0, 500.0));
+        assert_eq!(position(&world, e[1]), Vector2::new(510.0, 600.0));
+        assert_eq!(world.read::<MovingObject>().get(e[1]).unwrap().velocity.y, 0.0);
+
+        // touching now, still standing and nothing moves
+        world.write::<HasAABB>().get_mut(e[1]).unwrap().on_ground = false;
+        CollisionSystem.run_now(&mut world.res);
+        assert_eq!(position(&world, e[1]), Vector2::new(510.0, 600.0));
+        let bb = world.read::<HasAABB>();
+        assert!(bb.get(e[1]).unwrap().on_ground && bb.get(e[1]).unwrap().on_entity);
+        assert!(!bb.get(e[0]).unwrap().on_entity);
+        let events = world.read_resource::<CollisionEvents>();
+        assert_eq!((events.events[0].a, events.events[0].depth), (e[1], 0.0));
+    }
+
+    #[test]
+    fn positions_are_interpolated_between_steps() {
+        let mut world = World::new();
+        world.register::<MovingObject>();
+        world.register::<Position>();
+
+        let mut mv = MovingObject::new(Vector2::new(100.0, 50.0));
+        mv.old_position = Vector2::new(0.0, 10.0);
+        let e = world.create_entity().with(mv).with(Position::new(0.0, 0.0)).build();
+
+        PositionSystem { alpha: 0.25 }.run_now(&mut world.res);
+        let pos = world.read::<Position>().get(e).cloned().unwrap();
+        assert_eq!((pos.x, pos.y), (25.0, 20.0));
     }
 }
@@ -31,6 +31,17 @@ impl PlayerAnimations {
     pub fn slide() -> AnimationSequence {
         to_seq(Animation::play(0, 9))
     }
+
+    pub fn dead() -> AnimationSequence {
+        to_seq(Animation::seq(vec![
+            Animation::play(0, 9),
+            Animation::forever(Animation::play(9, 9)),
+        ]))
+    }
+
+    pub fn ledge() -> AnimationSequence {
+        to_seq(Animation::forever(Animation::play(0, 0)))
+    }
 }
 
 fn to_seq(a: Animation) -> AnimationSequence {
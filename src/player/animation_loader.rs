@@ -1,16 +1,30 @@
 use asset_storage::*;
+use combat::frame_hitboxes;
 use ggez::{Context, GameResult};
+use resources::AttackHitboxes;
 use sprite::Loader;
 use sprite::animation::Animation;
 
 pub struct AnimationLoader;
 impl AnimationLoader {
-    pub fn load_assets(ctx: &mut Context, asset_storage: &mut AssetStorage) -> GameResult<()> {
+    pub fn load_assets(
+        ctx: &mut Context,
+        asset_storage: &mut AssetStorage,
+        hitboxes: &mut AttackHitboxes,
+    ) -> GameResult<()> {
         let idle = Loader::load_sprite_sheet(ctx, "/idle")?;
         let attacking = Loader::load_sprite_sheet(ctx, "/attack")?;
         let jumping = Loader::load_sprite_sheet(ctx, "/jump")?;
         let running = Loader::load_sprite_sheet(ctx, "/run")?;
         let sliding = Loader::load_sprite_sheet(ctx, "/slide")?;
+        // no hang sprites yet, hanging holds the jump frame at the top of the rise
+        let mut hanging = Loader::load_sprite_sheet(ctx, "/jump")?;
+        let held = hanging.data.swap_remove(4);
+        hanging.data = vec![held];
+        // no death sprites yet, the slide ends lying on the ground
+        let dying = Loader::load_sprite_sheet(ctx, "/slide")?;
+
+        hitboxes.insert(P_ATTACK, frame_hitboxes(&attacking.data));
 
         asset_storage.animations.extend(vec![
             (P_IDLE, Animation::new(idle)),
@@ -18,6 +32,8 @@ impl AnimationLoader {
             (P_JUMP, Animation::new(jumping)),
             (P_RUN, Animation::new(running)),
             (P_SLIDE, Animation::new(sliding)),
+            (P_LEDGE, Animation::new(hanging)),
+            (P_DEAD, Animation::new(dying)),
         ]);
         Ok(())
     }
@@ -28,3 +44,5 @@ pub const P_ATTACK: &str = "player-attack";
 pub const P_JUMP: &str = "player-jump";
 pub const P_RUN: &str = "player-run";
 pub const P_SLIDE: &str = "player-slide";
+pub const P_LEDGE: &str = "player-ledge";
+pub const P_DEAD: &str = "player-dead";
@@ -1,10 +1,7 @@
+use player::consts as PC;
 use player::state_machine::StateMachine;
 use specs::*;
 
-#[derive(Debug, Component, Default)]
-#[component(NullStorage)]
-pub struct Controlled;
-
 #[derive(Component)]
 #[component(DenseVecStorage)]
 pub struct PlayerStateMachine {
@@ -15,12 +12,89 @@ pub struct PlayerStateMachine {
 #[component(HashMapStorage)]
 pub struct StartPSM;
 
-// #[derive(Component)]
-// #[component(DenseVecStorage)]
-// pub struct PlayerStats {
-//     pub health: f64,
-//     pub damage: f64,
-//     pub abilities: Vec<Ability>,
-//     pub buffs: Vec<Buff>,
-//     pub inventory: [4; [3; Item]],
-// }
\ No newline at end of file
+/// Movement abilities an entity has, checked by the player states.
+#[derive(Debug, Component, Clone)]
+#[component(DenseVecStorage)]
+pub struct Abilities {
+    pub double_jump: bool,
+    /// Jumps that can be made in the air before touching ground again.
+    pub air_jumps: usize,
+    pub air_jumps_left: usize,
+
+    pub wall_jump: bool,
+}
+
+impl Abilities {
+    pub fn none() -> Abilities {
+        Abilities {
+            double_jump: false,
+            air_jumps: 0,
+            air_jumps_left: 0,
+            wall_jump: false,
+        }
+    }
+
+    pub fn refill(&mut self) {
+        self.air_jumps_left = self.air_jumps;
+    }
+}
+
+impl Default for Abilities {
+    fn default() -> Abilities {
+        Abilities {
+            double_jump: true,
+            air_jumps: 1,
+            air_jumps_left: 1,
+            wall_jump: true,
+        }
+    }
+}
+
+/// How an entity moves, loaded from a profile file so characters can differ.
+#[derive(Debug, Component, Clone, Deserialize)]
+#[component(DenseVecStorage)]
+#[serde(default)]
+pub struct MovementProfile {
+    pub gravity: f64,
+    pub max_falling_speed: f64,
+    pub jump_speed: f64,
+    pub walk_speed: f64,
+    pub walk_accel: f64,
+    pub climb_speed: f64,
+    pub ledge_climb_speed: f64,
+    pub double_jump_scale: f64,
+    pub wall_slide_speed: f64,
+    pub wall_jump_speed: f64,
+    /// Frames after a wall jump the character can't steer back into the wall.
+    pub wall_jump_lock_frames: usize,
+    /// Frames after letting go of a ledge it can't be grabbed again.
+    pub ledge_drop_frames: usize,
+    pub water_gravity_scale: f64,
+    pub water_speed_scale: f64,
+
+    /// Profile file this was loaded from, reloading it updates the component.
+    #[serde(skip)]
+    pub source: Option<String>,
+}
+
+impl Default for MovementProfile {
+    fn default() -> MovementProfile {
+        MovementProfile {
+            gravity: PC::GRAVITY,
+            max_falling_speed: PC::MAX_FALLING_SPEED,
+            jump_speed: PC::JUMP_SPEED,
+            walk_speed: PC::WALK_SPEED,
+            walk_accel: PC::WALK_ACCEL,
+            climb_speed: PC::CLIMB_SPEED,
+            ledge_climb_speed: PC::LEDGE_CLIMB_SPEED,
+            double_jump_scale: PC::DOUBLE_JUMP_SCALE,
+            wall_slide_speed: PC::WALL_SLIDE_SPEED,
+            wall_jump_speed: PC::WALL_JUMP_SPEED,
+            wall_jump_lock_frames: PC::WALL_JUMP_LOCK_FRAMES,
+            ledge_drop_frames: PC::LEDGE_DROP_FRAMES,
+            water_gravity_scale: PC::WATER_GRAVITY_SCALE,
+            water_speed_scale: PC::WATER_SPEED_SCALE,
+            source: None,
+        }
+    }
+}
@@ -3,4 +3,12 @@ pub const MAX_FALLING_SPEED: f64 = -4000.0;
 pub const JUMP_SPEED: f64 = 1600.0;
 pub const WALK_SPEED: f64 = 1100.0;
 pub const WALK_ACCEL: f64 = 700.0;
-pub const JUMP_FRAMES_THRESHOLD: usize = 4;
+pub const CLIMB_SPEED: f64 = 500.0;
+pub const WATER_GRAVITY_SCALE: f64 = 0.35;
+pub const WATER_SPEED_SCALE: f64 = 0.5;
+pub const LEDGE_CLIMB_SPEED: f64 = 600.0;
+pub const LEDGE_DROP_FRAMES: usize = 3;
+pub const DOUBLE_JUMP_SCALE: f64 = 0.85;
+pub const WALL_SLIDE_SPEED: f64 = -400.0;
+pub const WALL_JUMP_SPEED: f64 = 900.0;
+pub const WALL_JUMP_LOCK_FRAMES: usize = 8;
@@ -3,35 +3,75 @@ pub mod systems;
 pub mod animation_defs;
 pub mod state_machine;
 pub mod state;
-mod consts;
+pub mod consts;
+pub mod profile;
 mod animation_loader;
 
 use self::animation_defs::*;
 pub use self::animation_loader::AnimationLoader;
 pub use self::consts::*;
+pub use self::profile::{PLAYER_PROFILE, ProfileReloadSystem, ProfileWatcher};
 
 use components::*;
+use layers;
 use physics::AABB;
-use specs::World;
+use specs::{EntityBuilder, World};
 use util::Vector2;
 
+pub const HEALTH: f64 = 100.0;
+pub const ATTACK_DAMAGE: f64 = 25.0;
+pub const ATTACK_KNOCKBACK_X: f64 = 900.0;
+pub const ATTACK_KNOCKBACK_Y: f64 = 700.0;
+pub const INVINCIBILITY_TIME: f64 = 0.6;
+pub const RESPAWN_TIME: f64 = 2.0;
+
 pub struct Player;
 impl Player {
     pub fn spawn(
         world: &mut World,
         location: Vector2,
-        controlled: bool,
+        device: Option<InputDevice>,
         camera_snap: bool,
         count: &mut usize,
     ) {
 
+        let profile = world.read_resource::<ProfileWatcher>().profile(PLAYER_PROFILE);
+
+        let e = Player::actor(world, location, 0.4, layers::PLAYERS, profile)
+            .with(Abilities::default())
+            .with(Health::new(HEALTH))
+            .with(Damage {
+                amount: ATTACK_DAMAGE,
+                knockback: Vector2::new(ATTACK_KNOCKBACK_X, ATTACK_KNOCKBACK_Y),
+                invincibility: INVINCIBILITY_TIME,
+                hits: vec![layers::ENEMIES],
+            });
+
+        let e = if camera_snap { e.with(SnapCamera) } else { e };
+        let e = match device {
+            Some(device) => e.with(Controlled { device }),
+            None => e,
+        };
+        e.build();
+
+        *count += 1;
+        println!("Players: {}", count);
+    }
+
+    /// Everything an actor driven by the player states needs, whoever presses the buttons.
+    pub fn actor(
+        world: &mut World,
+        location: Vector2,
+        scale: f64,
+        layer: usize,
+        profile: MovementProfile,
+    ) -> EntityBuilder {
         let psm = PlayerStateMachine { machine: state_machine::StateMachine::new(state::Idle) };
 
         let pos = Position::new(location.x as f32, location.y as f32);
-        let player_scale: f64 = 0.4;
-        let scalable = Scalable::new(player_scale as f32, player_scale as f32);
+        let scalable = Scalable::new(scale as f32, scale as f32);
 
-        let e = world
+        world
             .create_entity()
             .with(pos)
             .with(Renderable {
@@ -49,19 +89,11 @@ impl Player {
             .with(scalable)
             .with(MovingObject::new(location.clone()))
             .with(HasAABB::new(AABB::new_full(
-                Vector2::new(290.0, 500.0) * player_scale,
+                Vector2::new(290.0, 500.0) * scale,
                 Vector2::new(0.7, 0.8),
             )))
-            .with(CollisionDetection { group: 0 });
-
-        let e = if camera_snap { e.with(SnapCamera) } else { e };
-
-        // let e = if controlled { e.with(Controlled) } else { e };
-        // e.build();
-
-        e.with(Controlled).build();
-
-        *count += 1;
-        println!("Players: {}", count);
+            .with(CollisionDetection { layer, mass: 1.0 })
+            .with(InputState::new())
+            .with(profile)
     }
 }
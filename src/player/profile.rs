@@ -0,0 +1,180 @@
+use components::MovementProfile;
+use ggez::{GameError, GameResult};
+use serde_json;
+use specs::*;
+use std::fs::{self, File};
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+pub const PLAYER_PROFILE: &str = "movement/player.json";
+
+struct WatchedProfile {
+    name: &'static str,
+    modified: Option<SystemTime>,
+    profile: MovementProfile,
+}
+
+/// Keeps the movement profiles read from the resources dir, and notices when
+/// one of the files is saved again so it can be applied while the game runs.
+pub struct ProfileWatcher {
+    root: PathBuf,
+    watched: Vec<WatchedProfile>,
+}
+
+impl ProfileWatcher {
+    pub fn new(root: PathBuf) -> ProfileWatcher {
+        ProfileWatcher {
+            root,
+            watched: vec![],
+        }
+    }
+
+    /// Loads a profile and starts watching its file.
+    pub fn watch(&mut self, name: &'static str) -> GameResult<MovementProfile> {
+        if let Some(w) = self.watched.iter().find(|w| w.name == name) {
+            return Ok(w.profile.clone());
+        }
+
+        let modified = self.modified(name);
+        let profile = self.load(name)?;
+        self.watched.push(WatchedProfile {
+            name,
+            modified,
+            profile: profile.clone(),
+        });
+        Ok(profile)
+    }
+
+    /// Last loaded version of a watched profile, the defaults otherwise.
+    pub fn profile(&self, name: &str) -> MovementProfile {
+        self.watched
+            .iter()
+            .find(|w| w.name == name)
+            .map(|w| w.profile.clone())
+            .unwrap_or_default()
+    }
+
+    /// Reloads every profile whose file changed since the last look and returns them.
+    /// A file that doesn't parse is reported and the previous values are kept.
+    pub fn poll(&mut self) -> Vec<MovementProfile> {
+        let mut changed = vec![];
+
+        for i in 0..self.watched.len() {
+            let name = self.watched[i].name;
+            let modified = self.modified(name);
+            if modified == self.watched[i].modified {
+                continue;
+            }
+            self.watched[i].modified = modified;
+
+            match self.load(name) {
+                Ok(profile) => {
+                    println!("Reloaded movement profile: {}", name);
+                    self.watched[i].profile = profile.clone();
+                    changed.push(profile);
+                }
+                Err(e) => println!("Keeping old movement profile, {:?}", e),
+            }
+        }
+
+        changed
+    }
+
+    fn modified(&self, name: &str) -> Option<SystemTime> {
+        fs::metadata(self.root.join(name))
+            .and_then(|m| m.modified())
+            .ok()
+    }
+
+    fn load(&self, name: &str) -> GameResult<MovementProfile> {
+        let file = File::open(self.root.join(name))?;
+        let mut profile: MovementProfile = serde_json::from_reader(file).map_err(|e| {
+            GameError::ResourceLoadError(format!("{}: {}", name, e))
+        })?;
+        profile.source = Some(name.to_owned());
+        Ok(profile)
+    }
+}
+
+pub struct ProfileReloadSystem;
+impl<'a> System<'a> for ProfileReloadSystem {
+    type SystemData = (FetchMut<'a, ProfileWatcher>, WriteStorage<'a, MovementProfile>);
+
+    fn run(&mut self, (mut watcher, mut profiles): Self::SystemData) {
+        for changed in watcher.poll() {
+            for profile in (&mut profiles).join() {
+                if profile.source == changed.source {
+                    *profile = changed.clone();
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::io::Write;
+
+    fn write(root: &PathBuf, jump_speed: &str) {
+        let mut f = File::create(root.join(PLAYER_PROFILE)).unwrap();
+        write!(f, "{{ \"jump_speed\": {} }}", jump_speed).unwrap();
+    }
+
+    #[test]
+    fn reloads_profile_when_the_file_changes() {
+        let root = env::temp_dir().join("movement-profile-test");
+        fs::create_dir_all(root.join("movement")).unwrap();
+        write(&root, "1200.0");
+
+        let mut watcher = ProfileWatcher::new(root.clone());
+        let profile = watcher.watch(PLAYER_PROFILE).unwrap();
+        assert_eq!(profile.jump_speed, 1200.0);
+        assert_eq!(profile.walk_speed, MovementProfile::default().walk_speed);
+        assert!(watcher.poll().is_empty());
+
+        // pretend the last look happened before the save
+        watcher.watched[0].modified = None;
+        write(&root, "900.0");
+        let changed = watcher.poll();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].jump_speed, 900.0);
+        assert_eq!(watcher.profile(PLAYER_PROFILE).jump_speed, 900.0);
+
+        watcher.watched[0].modified = None;
+        write(&root, "oops");
+        assert!(watcher.poll().is_empty());
+        assert_eq!(watcher.profile(PLAYER_PROFILE).jump_speed, 900.0);
+    }
+
+    #[test]
+    fn reload_swaps_the_profile_of_entities_using_it() {
+        let root = env::temp_dir().join("movement-profile-reload-test");
+        fs::create_dir_all(root.join("movement")).unwrap();
+        write(&root, "1200.0");
+
+        let mut watcher = ProfileWatcher::new(root.clone());
+        let profile = watcher.watch(PLAYER_PROFILE).unwrap();
+
+        let mut world = World::new();
+        world.register::<MovementProfile>();
+        let player = world.create_entity().with(profile.clone()).build();
+        let other = MovementProfile {
+            source: Some("movement/other.json".to_owned()),
+            ..profile.clone()
+        };
+        let stranger = world.create_entity().with(other).build();
+        let plain = world.create_entity().with(MovementProfile::default()).build();
+
+        watcher.watched[0].modified = None;
+        write(&root, "900.0");
+        world.add_resource(watcher);
+        ProfileReloadSystem.run_now(&mut world.res);
+
+        let profiles = world.read::<MovementProfile>();
+        assert_eq!(profiles.get(player).unwrap().jump_speed, 900.0);
+        assert_eq!(profiles.get(stranger).unwrap().jump_speed, 1200.0);
+        assert_eq!(profiles.get(plain).unwrap().jump_speed, MovementProfile::default().jump_speed);
+    }
+}
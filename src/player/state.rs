@@ -1,10 +1,10 @@
 use components::*;
 use player;
 use player::animation_defs::*;
-use player::consts as PC;
 use player::state_machine::*;
 use player::systems::PlayerAux;
 use resources::*;
+use util::Vector2;
 
 pub struct Idle;
 
@@ -16,12 +16,15 @@ impl State for Idle {
         anim: &mut HasAnimationSequence,
         rend: &mut Renderable,
         _dir: &Directional,
-        _pi: &PlayerInput,
+        _pi: &InputState,
         _delta: &DeltaTime,
+        ab: &mut Abilities,
+        _jt: &JumpTuning,
+        _mp: &MovementProfile,
     ) {
         anim.sequence = PlayerAnimations::idle();
         rend.tpe.set_animation_id(player::animation_loader::P_IDLE, 10);
-        // player.dj.enable();
+        ab.refill();
     }
 
     fn on_resume(
@@ -31,10 +34,13 @@ impl State for Idle {
         anim: &mut HasAnimationSequence,
         rend: &mut Renderable,
         dir: &Directional,
-        pi: &PlayerInput,
+        pi: &InputState,
         delta: &DeltaTime,
+        ab: &mut Abilities,
+        jt: &JumpTuning,
+        mp: &MovementProfile,
     ) {
-        self.on_start(mv, bb, anim, rend, dir, pi, delta);
+        self.on_start(mv, bb, anim, rend, dir, pi, delta, ab, jt, mp);
     }
     /// Executed on every frame before updating, for use in reacting to events.
     fn handle_events(
@@ -44,19 +50,29 @@ impl State for Idle {
         anim: &mut HasAnimationSequence,
         _rend: &mut Renderable,
         _dir: &Directional,
-        pi: &PlayerInput,
-        _delta: &DeltaTime,
+        pi: &InputState,
+        delta: &DeltaTime,
+        _ab: &mut Abilities,
+        jt: &JumpTuning,
+        mp: &MovementProfile,
     ) -> Trans {
         let trans = if !bb.on_ground {
             anim.sequence = PlayerAnimations::drop();
             Trans::Push(Box::new(Jumping))
-        } else if pi.jump {
-            mv.velocity.y = PC::JUMP_SPEED;
+        } else if pi.down && bb.above_ladder {
+            Trans::Push(Box::new(Climbing))
+        } else if pi.down && bb.on_platform {
+            // down or down + jump
+            HumanoidMovement::drop_through(mv, bb);
+            anim.sequence = PlayerAnimations::drop();
+            Trans::Push(Box::new(Jumping))
+        } else if PlayerAux::wants_jump(bb, pi, delta, jt) {
+            PlayerAux::use_jump(bb, delta);
+            mv.velocity.y = mp.jump_speed;
             anim.sequence = PlayerAnimations::jump();
             Trans::Push(Box::new(Jumping))
-        } else if pi.down && bb.on_platform {
-            mv.position.y -= HumanoidMovement::PLATFORM_THRESHOLD;
-            Trans::None
+        } else if pi.up && bb.on_ladder {
+            Trans::Push(Box::new(Climbing))
         } else if pi.left ^ pi.right {
             Trans::Push(Box::new(Running))
         } else if pi.slide {
@@ -76,10 +92,13 @@ impl State for Idle {
         _anim: &mut HasAnimationSequence,
         _rend: &mut Renderable,
         _dir: &Directional,
-        _pi: &PlayerInput,
+        _pi: &InputState,
         _delta: &DeltaTime,
+        _ab: &mut Abilities,
+        _jt: &JumpTuning,
+        mp: &MovementProfile,
     ) -> Trans {
-        PlayerAux::slow_down(&mut *mv, true);
+        PlayerAux::slow_down(&mut *mv, true, mp);
         Trans::None
     }
 }
@@ -94,12 +113,15 @@ impl State for Running {
         anim: &mut HasAnimationSequence,
         rend: &mut Renderable,
         _dir: &Directional,
-        _pi: &PlayerInput,
+        _pi: &InputState,
         _delta: &DeltaTime,
+        ab: &mut Abilities,
+        _jt: &JumpTuning,
+        _mp: &MovementProfile,
     ) {
         anim.sequence = PlayerAnimations::run();
         rend.tpe.set_animation_id(player::animation_loader::P_RUN, 10);
-        // player.dj.enable();
+        ab.refill();
     }
 
     fn on_resume(
@@ -109,10 +131,13 @@ impl State for Running {
         anim: &mut HasAnimationSequence,
         rend: &mut Renderable,
         dir: &Directional,
-        pi: &PlayerInput,
+        pi: &InputState,
         delta: &DeltaTime,
+        ab: &mut Abilities,
+        jt: &JumpTuning,
+        mp: &MovementProfile,
     ) {
-        self.on_start(mv, bb, anim, rend, dir, pi, delta);
+        self.on_start(mv, bb, anim, rend, dir, pi, delta, ab, jt, mp);
     }
 
     fn handle_events(
@@ -122,8 +147,11 @@ impl State for Running {
         anim: &mut HasAnimationSequence,
         _rend: &mut Renderable,
         _dir: &Directional,
-        pi: &PlayerInput,
-        _delta: &DeltaTime,
+        pi: &InputState,
+        delta: &DeltaTime,
+        _ab: &mut Abilities,
+        jt: &JumpTuning,
+        mp: &MovementProfile,
     ) -> Trans {
         if !(pi.left ^ pi.right) {
             return Trans::Switch(Box::new(Idle));
@@ -132,13 +160,20 @@ impl State for Running {
         let trans = if !bb.on_ground {
             anim.sequence = PlayerAnimations::drop();
             Trans::Push(Box::new(Jumping))
-        } else if pi.jump {
-            mv.velocity.y = PC::JUMP_SPEED;
+        } else if pi.down && bb.above_ladder {
+            Trans::Push(Box::new(Climbing))
+        } else if pi.down && bb.on_platform {
+            // down or down + jump
+            HumanoidMovement::drop_through(mv, bb);
+            anim.sequence = PlayerAnimations::drop();
+            Trans::Push(Box::new(Jumping))
+        } else if PlayerAux::wants_jump(bb, pi, delta, jt) {
+            PlayerAux::use_jump(bb, delta);
+            mv.velocity.y = mp.jump_speed;
             anim.sequence = PlayerAnimations::jump();
             Trans::Push(Box::new(Jumping))
-        } else if pi.down && bb.on_platform {
-            mv.position.y -= HumanoidMovement::PLATFORM_THRESHOLD * 2.0;
-            Trans::None
+        } else if pi.up && bb.on_ladder {
+            Trans::Push(Box::new(Climbing))
         } else if pi.slide {
             Trans::Push(Box::new(Sliding))
         } else if pi.attack {
@@ -157,10 +192,13 @@ impl State for Running {
         _anim: &mut HasAnimationSequence,
         _rend: &mut Renderable,
         dir: &Directional,
-        _pi: &PlayerInput,
+        pi: &InputState,
         _delta: &DeltaTime,
+        _ab: &mut Abilities,
+        _jt: &JumpTuning,
+        mp: &MovementProfile,
     ) -> Trans {
-        PlayerAux::movement(&mut *mv, &mut *bb, &dir);
+        PlayerAux::movement(&mut *mv, &mut *bb, &dir, pi, mp);
         Trans::None
     }
 }
@@ -175,13 +213,13 @@ impl State for Jumping {
         _anim: &mut HasAnimationSequence,
         rend: &mut Renderable,
         _dir: &Directional,
-        _pi: &PlayerInput,
+        _pi: &InputState,
         _delta: &DeltaTime,
+        _ab: &mut Abilities,
+        _jt: &JumpTuning,
+        _mp: &MovementProfile,
     ) {
         rend.tpe.set_animation_id(player::animation_loader::P_JUMP, 10);
-        if !bb.on_ground && bb.was_on_ground {
-            bb.frames_from_jump_start = 0;
-        }
     }
 
     fn on_resume(
@@ -191,21 +229,27 @@ impl State for Jumping {
         anim: &mut HasAnimationSequence,
         rend: &mut Renderable,
         dir: &Directional,
-        pi: &PlayerInput,
+        pi: &InputState,
         delta: &DeltaTime,
+        ab: &mut Abilities,
+        jt: &JumpTuning,
+        mp: &MovementProfile,
     ) {
-        self.on_start(mv, bb, anim, rend, dir, pi, delta)
+        self.on_start(mv, bb, anim, rend, dir, pi, delta, ab, jt, mp)
     }
 
     fn handle_events(
         &mut self,
         mv: &mut MovingObject,
         bb: &mut HasAABB,
-        _anim: &mut HasAnimationSequence,
+        anim: &mut HasAnimationSequence,
         _rend: &mut Renderable,
         dir: &Directional,
-        pi: &PlayerInput,
-        _delta: &DeltaTime,
+        pi: &InputState,
+        delta: &DeltaTime,
+        ab: &mut Abilities,
+        jt: &JumpTuning,
+        mp: &MovementProfile,
     ) -> Trans {
         let mut no_left = false;
         if bb.cannot_go_left_frames > 0 {
@@ -220,21 +264,32 @@ impl State for Jumping {
         };
 
         if (pi.left && !no_left) ^ (pi.right && !no_right) {
-            PlayerAux::movement(&mut *mv, &mut *bb, &dir);
+            PlayerAux::movement(&mut *mv, &mut *bb, &dir, pi, mp);
         };
 
         let trans = if pi.attack {
             Trans::Switch(Box::new(Attacking))
-        } else if pi.jump {
-            if bb.frames_from_jump_start <= PC::JUMP_FRAMES_THRESHOLD && mv.velocity.y <= 0.0 &&
-                !bb.at_ceiling
-            {
-                mv.velocity.y = PC::JUMP_SPEED;
-                Trans::None
-            } else {
-                // player.dj.double_jump(&mut mv);
-                Trans::None
+        } else if pi.up && bb.on_ladder {
+            Trans::Switch(Box::new(Climbing))
+        } else if PlayerAux::wants_jump(bb, pi, delta, jt) && bb.in_water {
+            PlayerAux::use_jump(bb, delta);
+            mv.velocity.y = mp.jump_speed * mp.water_speed_scale;
+            Trans::None
+        } else if PlayerAux::wants_jump(bb, pi, delta, jt) {
+            if PlayerAux::in_coyote_time(mv, bb, delta, jt) {
+                PlayerAux::use_jump(bb, delta);
+                mv.velocity.y = mp.jump_speed;
+            } else if ab.wall_jump && (bb.pushes_left_wall || bb.pushes_right_wall) {
+                PlayerAux::use_jump(bb, delta);
+                Jumping::wall_jump(mv, bb, mp);
+            } else if ab.double_jump && ab.air_jumps_left > 0 {
+                PlayerAux::use_jump(bb, delta);
+                ab.air_jumps_left -= 1;
+                mv.velocity.y = mp.jump_speed * mp.double_jump_scale;
+                anim.sequence = PlayerAnimations::jump();
             }
+            // otherwise the press stays buffered for landing
+            Trans::None
         } else {
             Trans::None
         };
@@ -248,45 +303,160 @@ impl State for Jumping {
         _anim: &mut HasAnimationSequence,
         _rend: &mut Renderable,
         _dir: &Directional,
-        pi: &PlayerInput,
+        pi: &InputState,
         time: &DeltaTime,
+        ab: &mut Abilities,
+        jt: &JumpTuning,
+        mp: &MovementProfile,
     ) -> Trans {
-        let y_vel = PC::GRAVITY * time.delta + mv.velocity.y;
-        mv.velocity.y = y_vel.max(PC::MAX_FALLING_SPEED);
-        // let gl = player.lg.grab_ledge(&mut mv, &pi, terrain);
+        let (gravity, max_falling_speed) = if bb.in_water {
+            (
+                mp.gravity * mp.water_gravity_scale,
+                mp.max_falling_speed * mp.water_speed_scale,
+            )
+        } else {
+            (mp.gravity, mp.max_falling_speed)
+        };
+        let y_vel = gravity * time.delta + mv.velocity.y;
+        mv.velocity.y = y_vel.max(max_falling_speed);
+
+        if ab.wall_jump && Jumping::slides_down_wall(bb, pi) {
+            mv.velocity.y = mv.velocity.y.max(mp.wall_slide_speed);
+        }
+
+        // letting go of jump early makes for a shorter jump, other rises are left alone
+        if bb.input_jump && !pi.jump_held {
+            mv.velocity.y = mv.velocity.y.min(jt.release_speed);
+            bb.input_jump = false;
+        }
 
         let trans = if bb.on_ground {
+            bb.input_jump = false;
             Trans::Pop
-        } else
-        /*if gl {
+        } else if LedgeGrab::can_grab(mv, bb, pi) {
             Trans::Switch(Box::new(LedgeGrab))
-        } else*/
-        if !(pi.left ^ pi.right) {
-            PlayerAux::slow_down(&mut *mv, false);
+        } else if !(pi.left ^ pi.right) {
+            PlayerAux::slow_down(&mut *mv, false, mp);
             Trans::None
         } else {
             Trans::None
         };
         trans
     }
+}
 
-    fn fixed_update(
+impl Jumping {
+    fn slides_down_wall(bb: &HasAABB, pi: &InputState) -> bool {
+        (pi.left && bb.pushes_left_wall) || (pi.right && bb.pushes_right_wall)
+    }
+
+    // Kicks off the wall and briefly ignores input toward it, so holding the
+    // direction doesn't pull the player straight back.
+    fn wall_jump(mv: &mut MovingObject, bb: &mut HasAABB, mp: &MovementProfile) {
+        mv.velocity.y = mp.jump_speed;
+        // the kick always goes its full height
+        bb.input_jump = false;
+        mv.accel.x = 0.0;
+        if bb.pushes_left_wall {
+            mv.velocity.x = mp.wall_jump_speed;
+            bb.cannot_go_left_frames = mp.wall_jump_lock_frames;
+        } else {
+            mv.velocity.x = -mp.wall_jump_speed;
+            bb.cannot_go_right_frames = mp.wall_jump_lock_frames;
+        }
+    }
+}
+
+pub struct Climbing;
+
+impl State for Climbing {
+    fn on_start(
+        &mut self,
+        mv: &mut MovingObject,
+        bb: &mut HasAABB,
+        anim: &mut HasAnimationSequence,
+        rend: &mut Renderable,
+        _dir: &Directional,
+        _pi: &InputState,
+        _delta: &DeltaTime,
+        ab: &mut Abilities,
+        _jt: &JumpTuning,
+        _mp: &MovementProfile,
+    ) {
+        bb.climbing = true;
+        ab.refill();
+        PlayerAux::stop(mv);
+        mv.velocity.y = 0.0;
+        anim.sequence = PlayerAnimations::idle();
+        rend.tpe.set_animation_id(player::animation_loader::P_IDLE, 10);
+    }
+
+    fn on_resume(
+        &mut self,
+        mv: &mut MovingObject,
+        bb: &mut HasAABB,
+        anim: &mut HasAnimationSequence,
+        rend: &mut Renderable,
+        dir: &Directional,
+        pi: &InputState,
+        delta: &DeltaTime,
+        ab: &mut Abilities,
+        jt: &JumpTuning,
+        mp: &MovementProfile,
+    ) {
+        self.on_start(mv, bb, anim, rend, dir, pi, delta, ab, jt, mp);
+    }
+
+    fn handle_events(
         &mut self,
         mv: &mut MovingObject,
         bb: &mut HasAABB,
+        anim: &mut HasAnimationSequence,
+        _rend: &mut Renderable,
+        _dir: &Directional,
+        pi: &InputState,
+        delta: &DeltaTime,
+        _ab: &mut Abilities,
+        jt: &JumpTuning,
+        mp: &MovementProfile,
+    ) -> Trans {
+        if PlayerAux::wants_jump(bb, pi, delta, jt) {
+            PlayerAux::use_jump(bb, delta);
+            bb.climbing = false;
+            mv.velocity.y = mp.jump_speed;
+            anim.sequence = PlayerAnimations::jump();
+            Trans::Switch(Box::new(Jumping))
+        } else if !(bb.on_ladder || bb.above_ladder) || (bb.on_ground && pi.down) {
+            bb.climbing = false;
+            mv.velocity.y = 0.0;
+            Trans::Pop
+        } else {
+            Trans::None
+        }
+    }
+
+    fn update(
+        &mut self,
+        mv: &mut MovingObject,
+        _bb: &mut HasAABB,
         _anim: &mut HasAnimationSequence,
         _rend: &mut Renderable,
         _dir: &Directional,
-        _pi: &PlayerInput,
+        pi: &InputState,
         _delta: &DeltaTime,
+        _ab: &mut Abilities,
+        _jt: &JumpTuning,
+        mp: &MovementProfile,
     ) -> Trans {
-        if bb.frames_from_jump_start <= PC::JUMP_FRAMES_THRESHOLD {
-            if bb.at_ceiling || mv.velocity.y > 0.0 {
-                bb.frames_from_jump_start = PC::JUMP_FRAMES_THRESHOLD + 1;
-            }
-        }
+        let axis = |neg: bool, pos: bool| if neg ^ pos {
+            if neg { -1.0 } else { 1.0 }
+        } else {
+            0.0
+        };
 
-        bb.frames_from_jump_start += 1;
+        mv.accel.x = 0.0;
+        mv.velocity.x = axis(pi.left, pi.right) * mp.climb_speed / 2.0;
+        mv.velocity.y = axis(pi.down, pi.up) * mp.climb_speed;
         Trans::None
     }
 }
@@ -301,8 +471,11 @@ impl State for Sliding {
         anim: &mut HasAnimationSequence,
         rend: &mut Renderable,
         _dir: &Directional,
-        _pi: &PlayerInput,
+        _pi: &InputState,
         _delta: &DeltaTime,
+        _ab: &mut Abilities,
+        _jt: &JumpTuning,
+        _mp: &MovementProfile,
     ) {
         anim.sequence = PlayerAnimations::slide();
         rend.tpe.set_animation_id(player::animation_loader::P_SLIDE, 10);
@@ -311,14 +484,18 @@ impl State for Sliding {
     fn handle_events(
         &mut self,
         _mv: &mut MovingObject,
-        _bb: &mut HasAABB,
+        bb: &mut HasAABB,
         anim: &mut HasAnimationSequence,
         _rend: &mut Renderable,
         _dir: &Directional,
-        pi: &PlayerInput,
-        _delta: &DeltaTime,
+        pi: &InputState,
+        delta: &DeltaTime,
+        _ab: &mut Abilities,
+        jt: &JumpTuning,
+        _mp: &MovementProfile,
     ) -> Trans {
-        let trans = if pi.jump {
+        // the press is left for Jumping to act on
+        let trans = if PlayerAux::wants_jump(bb, pi, delta, jt) {
             anim.sequence = PlayerAnimations::jump();
             Trans::Switch(Box::new(Jumping))
         } else {
@@ -334,8 +511,11 @@ impl State for Sliding {
         _anim: &mut HasAnimationSequence,
         _rend: &mut Renderable,
         _dir: &Directional,
-        _pi: &PlayerInput,
+        _pi: &InputState,
         _delta: &DeltaTime,
+        _ab: &mut Abilities,
+        _jt: &JumpTuning,
+        _mp: &MovementProfile,
     ) -> Trans {
         Trans::None
     }
@@ -347,8 +527,11 @@ impl State for Sliding {
         anim: &mut HasAnimationSequence,
         _rend: &mut Renderable,
         _dir: &Directional,
-        _pi: &PlayerInput,
+        _pi: &InputState,
         _delta: &DeltaTime,
+        _ab: &mut Abilities,
+        _jt: &JumpTuning,
+        _mp: &MovementProfile,
     ) -> Trans {
         if anim.sequence.is_over() {
             Trans::Pop
@@ -374,8 +557,11 @@ impl State for Attacking {
         anim: &mut HasAnimationSequence,
         rend: &mut Renderable,
         _dir: &Directional,
-        _pi: &PlayerInput,
+        _pi: &InputState,
         _delta: &DeltaTime,
+        _ab: &mut Abilities,
+        _jt: &JumpTuning,
+        _mp: &MovementProfile,
     ) {
         rend.tpe.set_animation_id(player::animation_loader::P_ATTACK, 10);
         anim.sequence = PlayerAnimations::attack();
@@ -388,8 +574,11 @@ impl State for Attacking {
         _anim: &mut HasAnimationSequence,
         _rend: &mut Renderable,
         _dir: &Directional,
-        _pi: &PlayerInput,
+        _pi: &InputState,
         _delta: &DeltaTime,
+        _ab: &mut Abilities,
+        _jt: &JumpTuning,
+        _mp: &MovementProfile,
     ) -> Trans {
         // let t = if self.can_cancel(player) {
         //     if pi.jump {
@@ -415,8 +604,11 @@ impl State for Attacking {
         anim: &mut HasAnimationSequence,
         _rend: &mut Renderable,
         _dir: &Directional,
-        _pi: &PlayerInput,
+        _pi: &InputState,
         _delta: &DeltaTime,
+        _ab: &mut Abilities,
+        _jt: &JumpTuning,
+        _mp: &MovementProfile,
     ) -> Trans {
         if anim.sequence.is_over() {
             Trans::Pop
@@ -432,61 +624,589 @@ impl State for Attacking {
         _anim: &mut HasAnimationSequence,
         _rend: &mut Renderable,
         _dir: &Directional,
-        _pi: &PlayerInput,
+        _pi: &InputState,
         _delta: &DeltaTime,
+        _ab: &mut Abilities,
+        _jt: &JumpTuning,
+        _mp: &MovementProfile,
     ) -> Trans {
         Trans::None
     }
 }
 
-// pub struct LedgeGrab;
+pub struct LedgeGrab;
 
-// impl State for LedgeGrab {
-//     fn on_start(&mut self,   mv: &mut MovingObject,         bb: &mut HasAABB,         anim: &mut HasAnimationSequence,         rend: &mut Renderable,         dir: &Directional,         pi: &PlayerInput,         delta: &DeltaTime,) {
-//         player.data.idle.reset();
-//         player.dj.enable();
-//     }
-//     fn on_resume(&mut self,   mv: &mut MovingObject,         bb: &mut HasAABB,         anim: &mut HasAnimationSequence,         rend: &mut Renderable,         dir: &Directional,         pi: &PlayerInput,         delta: &DeltaTime,) {
-//         self.on_start(player);
-//     }
-//     /// Executed on every frame before updating, for use in reacting to events.
-//     fn handle_events(&mut self, _  mv: &mut MovingObject,         bb: &mut HasAABB,         anim: &mut HasAnimationSequence,         rend: &mut Renderable,         dir: &Directional,         pi: &PlayerInput,         delta: &DeltaTime,) -> Trans {
-//         Trans::None
-//     }
+impl LedgeGrab {
+    pub fn can_grab(mv: &MovingObject, bb: &HasAABB, pi: &InputState) -> bool {
+        match bb.ledge {
+            Some(ledge) => {
+                let toward = if ledge.on_left {
+                    pi.left && !pi.right
+                } else {
+                    pi.right && !pi.left
+                };
+                toward && mv.velocity.y <= 0.0 && bb.cannot_go_left_frames == 0 &&
+                    bb.cannot_go_right_frames == 0
+            }
+            None => false,
+        }
+    }
+}
 
-//     fn update(&mut self,   mv: &mut MovingObject,         bb: &mut HasAABB,         anim: &mut HasAnimationSequence,         rend: &mut Renderable,         dir: &Directional,         pi: &PlayerInput,         delta: &DeltaTime,, duration: &Duration, terrain: &Terrain) -> Trans {
-//         mv.update_physics(duration, terrain);
-
-//         let ledge_on_left =
-//             player.lg.ledge_tile.0 as f64 * terrain.tile_size < mv.position.x;
-//         let ledge_on_right = !ledge_on_left;
-
-//         let state = if pi.down || (pi.right && ledge_on_left) ||
-//             (pi.left && ledge_on_right)
-//         {
-//             if ledge_on_left {
-//                 mv.cannot_go_left_frames = 3;
-//             } else {
-//                 mv.cannot_go_right_frames = 3;
-//             };
-//             Trans::Switch(Box::new(Jumping))
-//         } else if pi.jump {
-//             mv.velocity.y = Player::JUMP_SPEED;
-//             Trans::Switch(Box::new(Jumping))
-//         } else {
-//             Trans::None
-//         };
-
-//         pi.reset_actions();
-//         state
-//     }
+impl State for LedgeGrab {
+    fn on_start(
+        &mut self,
+        mv: &mut MovingObject,
+        bb: &mut HasAABB,
+        anim: &mut HasAnimationSequence,
+        rend: &mut Renderable,
+        _dir: &Directional,
+        _pi: &InputState,
+        _delta: &DeltaTime,
+        ab: &mut Abilities,
+        _jt: &JumpTuning,
+        _mp: &MovementProfile,
+    ) {
+        if let Some(ledge) = bb.ledge {
+            mv.position = ledge.hang_position(&bb.aabb);
+        }
+        ab.refill();
+        mv.velocity = Vector2::new(0.0, 0.0);
+        mv.accel = Vector2::new(0.0, 0.0);
+        anim.sequence = PlayerAnimations::ledge();
+        rend.tpe.set_animation_id(player::animation_loader::P_LEDGE, 1);
+    }
 
-//     fn fixed_update(&mut self,   mv: &mut MovingObject,         bb: &mut HasAABB,         anim: &mut HasAnimationSequence,         rend: &mut Renderable,         dir: &Directional,         pi: &PlayerInput,         delta: &DeltaTime,) -> Trans {
-//         player.data.idle.roll_frames();
-//         Trans::None
-//     }
+    fn on_resume(
+        &mut self,
+        mv: &mut MovingObject,
+        bb: &mut HasAABB,
+        anim: &mut HasAnimationSequence,
+        rend: &mut Renderable,
+        dir: &Directional,
+        pi: &InputState,
+        delta: &DeltaTime,
+        ab: &mut Abilities,
+        jt: &JumpTuning,
+        mp: &MovementProfile,
+    ) {
+        self.on_start(mv, bb, anim, rend, dir, pi, delta, ab, jt, mp);
+    }
 
-//     fn draw(&mut self, ctx: &mut Context, player: &Player, camera: &Camera) {
-//         draw_animation_frame(player, ctx, camera, &player.data.idle, &player.direction).unwrap();
-//     }
-// }
+    fn handle_events(
+        &mut self,
+        mv: &mut MovingObject,
+        bb: &mut HasAABB,
+        anim: &mut HasAnimationSequence,
+        _rend: &mut Renderable,
+        _dir: &Directional,
+        pi: &InputState,
+        delta: &DeltaTime,
+        _ab: &mut Abilities,
+        jt: &JumpTuning,
+        mp: &MovementProfile,
+    ) -> Trans {
+        let ledge = match bb.ledge {
+            Some(ledge) => ledge,
+            None => return Trans::Switch(Box::new(Jumping)),
+        };
+        let (toward, away) = if ledge.on_left {
+            (pi.left && !pi.right, pi.right && !pi.left)
+        } else {
+            (pi.right && !pi.left, pi.left && !pi.right)
+        };
+
+        if PlayerAux::wants_jump(bb, pi, delta, jt) {
+            PlayerAux::use_jump(bb, delta);
+            if away {
+                let dir = if ledge.on_left {
+                    Directional::Right
+                } else {
+                    Directional::Left
+                };
+                PlayerAux::movement(mv, bb, &dir, pi, mp);
+            }
+            LedgeGrab::let_go(bb, &ledge, mp);
+            mv.velocity.y = mp.jump_speed;
+            anim.sequence = PlayerAnimations::jump();
+            Trans::Switch(Box::new(Jumping))
+        } else if pi.down || away {
+            LedgeGrab::let_go(bb, &ledge, mp);
+            anim.sequence = PlayerAnimations::drop();
+            Trans::Switch(Box::new(Jumping))
+        } else if pi.up || toward {
+            Trans::Switch(Box::new(LedgeClimb { ledge }))
+        } else {
+            Trans::None
+        }
+    }
+
+    fn update(
+        &mut self,
+        mv: &mut MovingObject,
+        _bb: &mut HasAABB,
+        _anim: &mut HasAnimationSequence,
+        _rend: &mut Renderable,
+        _dir: &Directional,
+        _pi: &InputState,
+        _delta: &DeltaTime,
+        _ab: &mut Abilities,
+        _jt: &JumpTuning,
+        _mp: &MovementProfile,
+    ) -> Trans {
+        mv.velocity = Vector2::new(0.0, 0.0);
+        Trans::None
+    }
+}
+
+impl LedgeGrab {
+    // keeps the player from grabbing the same ledge right away
+    fn let_go(bb: &mut HasAABB, ledge: &Ledge, mp: &MovementProfile) {
+        if ledge.on_left {
+            bb.cannot_go_left_frames = mp.ledge_drop_frames;
+        } else {
+            bb.cannot_go_right_frames = mp.ledge_drop_frames;
+        }
+    }
+}
+
+/// Pulls the player up over the ledge: straight up until the feet clear the
+/// corner, then onto the ledge.
+pub struct LedgeClimb {
+    pub ledge: Ledge,
+}
+
+impl State for LedgeClimb {
+    fn on_start(
+        &mut self,
+        mv: &mut MovingObject,
+        _bb: &mut HasAABB,
+        _anim: &mut HasAnimationSequence,
+        _rend: &mut Renderable,
+        _dir: &Directional,
+        _pi: &InputState,
+        _delta: &DeltaTime,
+        _ab: &mut Abilities,
+        _jt: &JumpTuning,
+        _mp: &MovementProfile,
+    ) {
+        mv.accel = Vector2::new(0.0, 0.0);
+    }
+
+    fn handle_events(
+        &mut self,
+        _mv: &mut MovingObject,
+        _bb: &mut HasAABB,
+        _anim: &mut HasAnimationSequence,
+        _rend: &mut Renderable,
+        _dir: &Directional,
+        _pi: &InputState,
+        _delta: &DeltaTime,
+        _ab: &mut Abilities,
+        _jt: &JumpTuning,
+        _mp: &MovementProfile,
+    ) -> Trans {
+        Trans::None
+    }
+
+    fn update(
+        &mut self,
+        mv: &mut MovingObject,
+        bb: &mut HasAABB,
+        _anim: &mut HasAnimationSequence,
+        _rend: &mut Renderable,
+        _dir: &Directional,
+        _pi: &InputState,
+        _delta: &DeltaTime,
+        _ab: &mut Abilities,
+        _jt: &JumpTuning,
+        mp: &MovementProfile,
+    ) -> Trans {
+        let foot_y = mv.position.y + bb.aabb.offset.y - bb.aabb.half_size.y;
+        let center_x = mv.position.x + bb.aabb.offset.x;
+        let side = if self.ledge.on_left { -1.0 } else { 1.0 };
+
+        if foot_y <= self.ledge.corner.y {
+            mv.velocity = Vector2::new(0.0, mp.ledge_climb_speed);
+            Trans::None
+        } else if (center_x - self.ledge.corner.x) * side < bb.aabb.half_size.x {
+            mv.velocity = Vector2::new(side * mp.ledge_climb_speed, 0.0);
+            Trans::None
+        } else {
+            mv.velocity = Vector2::new(0.0, 0.0);
+            Trans::Pop
+        }
+    }
+}
+
+/// Out of health, the body stays where it falls and ignores all input.
+pub struct Dead;
+
+impl State for Dead {
+    fn on_start(
+        &mut self,
+        mv: &mut MovingObject,
+        bb: &mut HasAABB,
+        anim: &mut HasAnimationSequence,
+        rend: &mut Renderable,
+        _dir: &Directional,
+        _pi: &InputState,
+        _delta: &DeltaTime,
+        _ab: &mut Abilities,
+        _jt: &JumpTuning,
+        _mp: &MovementProfile,
+    ) {
+        bb.climbing = false;
+        mv.accel = Vector2::new(0.0, 0.0);
+        anim.sequence = PlayerAnimations::dead();
+        rend.tpe.set_animation_id(player::animation_loader::P_DEAD, 10);
+    }
+
+    fn update(
+        &mut self,
+        mv: &mut MovingObject,
+        _bb: &mut HasAABB,
+        _anim: &mut HasAnimationSequence,
+        _rend: &mut Renderable,
+        _dir: &Directional,
+        _pi: &InputState,
+        time: &DeltaTime,
+        _ab: &mut Abilities,
+        _jt: &JumpTuning,
+        mp: &MovementProfile,
+    ) -> Trans {
+        let y_vel = mp.gravity * time.delta + mv.velocity.y;
+        mv.velocity.y = y_vel.max(mp.max_falling_speed);
+        PlayerAux::slow_down(&mut *mv, true, mp);
+        Trans::None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use physics::AABB;
+
+    struct Actor {
+        mv: MovingObject,
+        bb: HasAABB,
+        anim: HasAnimationSequence,
+        rend: Renderable,
+        dir: Directional,
+        pi: InputState,
+        delta: DeltaTime,
+        ab: Abilities,
+        tuning: JumpTuning,
+        profile: MovementProfile,
+    }
+
+    impl Actor {
+        // hanging from a ledge on the right with the corner at (192, 448)
+        fn hanging() -> Actor {
+            let mut bb = HasAABB::new(AABB::new_full(
+                Vector2::new(64.0, 128.0),
+                Vector2::new(1.0, 1.0),
+            ));
+            bb.pushes_right_wall = true;
+            bb.ledge = Some(Ledge {
+                corner: Vector2::new(192.0, 448.0),
+                on_left: false,
+            });
+            let mut mv = MovingObject::new(Vector2::new(150.0, 400.0));
+            mv.velocity = Vector2::new(0.0, -300.0);
+
+            Actor {
+                mv,
+                bb,
+                anim: HasAnimationSequence {
+                    sequence: PlayerAnimations::drop(),
+                },
+                rend: Renderable {
+                    layer: 5,
+                    tpe: RenderableType::Animation {
+                        id: player::animation_loader::P_JUMP,
+                        frame: 0,
+                        length: 10,
+                    },
+                },
+                dir: Directional::Right,
+                pi: InputState::new(),
+                delta: DeltaTime {
+                    delta: 0.1,
+                    now: 10.0,
+                },
+                ab: Abilities::default(),
+                tuning: JumpTuning::new(),
+                profile: MovementProfile::default(),
+            }
+        }
+
+        fn start(&mut self, state: &mut State) {
+            state.on_start(
+                &mut self.mv,
+                &mut self.bb,
+                &mut self.anim,
+                &mut self.rend,
+                &self.dir,
+                &self.pi,
+                &self.delta,
+                &mut self.ab,
+                &self.tuning,
+                &self.profile,
+            );
+        }
+
+        fn handle_events(&mut self, state: &mut State) -> Trans {
+            state.handle_events(
+                &mut self.mv,
+                &mut self.bb,
+                &mut self.anim,
+                &mut self.rend,
+                &self.dir,
+                &self.pi,
+                &self.delta,
+                &mut self.ab,
+                &self.tuning,
+                &self.profile,
+            )
+        }
+
+        fn update(&mut self, state: &mut State) -> Trans {
+            state.update(
+                &mut self.mv,
+                &mut self.bb,
+                &mut self.anim,
+                &mut self.rend,
+                &self.dir,
+                &self.pi,
+                &self.delta,
+                &mut self.ab,
+                &self.tuning,
+                &self.profile,
+            )
+        }
+    }
+
+    fn is_switch(t: Trans) -> bool {
+        match t {
+            Trans::Switch(_) => true,
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn grabs_only_when_pushing_toward_the_ledge() {
+        let mut a = Actor::hanging();
+        assert!(!LedgeGrab::can_grab(&a.mv, &a.bb, &a.pi));
+        a.pi.right = true;
+        assert!(LedgeGrab::can_grab(&a.mv, &a.bb, &a.pi));
+        a.mv.velocity.y = 100.0;
+        assert!(!LedgeGrab::can_grab(&a.mv, &a.bb, &a.pi));
+    }
+
+    #[test]
+    fn hangs_still_at_the_corner() {
+        let mut a = Actor::hanging();
+        a.start(&mut LedgeGrab);
+        assert_eq!(a.mv.position, Vector2::new(160.0, 384.0));
+        assert_eq!(a.mv.velocity, Vector2::new(0.0, 0.0));
+        match a.rend.tpe {
+            RenderableType::Animation { id, .. } => {
+                assert_eq!(id, player::animation_loader::P_LEDGE)
+            }
+            _ => panic!("not an animation"),
+        }
+        match a.handle_events(&mut LedgeGrab) {
+            Trans::None => (),
+            _ => panic!("should keep hanging"),
+        }
+    }
+
+    #[test]
+    fn drops_and_jumps_off() {
+        let mut a = Actor::hanging();
+        a.start(&mut LedgeGrab);
+        a.pi.down = true;
+        assert!(is_switch(a.handle_events(&mut LedgeGrab)));
+        assert_eq!(a.bb.cannot_go_right_frames, a.profile.ledge_drop_frames);
+        assert!(!LedgeGrab::can_grab(&a.mv, &a.bb, &a.pi));
+
+        let mut a = Actor::hanging();
+        a.start(&mut LedgeGrab);
+        a.pi.press_jump(a.delta.now);
+        a.pi.left = true;
+        assert!(is_switch(a.handle_events(&mut LedgeGrab)));
+        assert_eq!(a.mv.velocity.y, a.profile.jump_speed);
+        assert!(a.mv.velocity.x < 0.0);
+    }
+
+    #[test]
+    fn climbs_up_and_over() {
+        let mut a = Actor::hanging();
+        a.start(&mut LedgeGrab);
+        a.pi.up = true;
+        assert!(is_switch(a.handle_events(&mut LedgeGrab)));
+
+        let mut climb = LedgeClimb {
+            ledge: a.bb.ledge.unwrap(),
+        };
+        let mut frames = 0;
+        loop {
+            match a.update(&mut climb) {
+                Trans::Pop => break,
+                _ => {
+                    let delta = a.delta.delta;
+                    a.mv.position += a.mv.velocity * delta;
+                }
+            }
+            frames += 1;
+            assert!(frames < 100, "never got over the ledge");
+        }
+
+        let foot_y = a.mv.position.y - a.bb.aabb.half_size.y;
+        assert!(foot_y > 448.0);
+        assert!(a.mv.position.x - a.bb.aabb.half_size.x >= 192.0);
+    }
+
+    fn airborne() -> Actor {
+        let mut a = Actor::hanging();
+        a.bb.ledge = None;
+        a.bb.pushes_right_wall = false;
+        a
+    }
+
+    #[test]
+    fn double_jump_uses_up_air_jumps() {
+        let mut a = airborne();
+        a.pi.press_jump(a.delta.now);
+        a.handle_events(&mut Jumping);
+        assert_eq!(a.mv.velocity.y, a.profile.jump_speed * a.profile.double_jump_scale);
+        assert_eq!(a.ab.air_jumps_left, 0);
+
+        a.mv.velocity.y = -300.0;
+        a.handle_events(&mut Jumping);
+        assert_eq!(a.mv.velocity.y, -300.0);
+
+        a.pi.release_jump(a.delta.now);
+        a.delta.now += 0.05;
+        a.pi.press_jump(a.delta.now);
+        a.handle_events(&mut Jumping);
+        assert_eq!(a.mv.velocity.y, -300.0);
+
+        a.start(&mut Idle);
+        assert_eq!(a.ab.air_jumps_left, a.ab.air_jumps);
+
+        let mut a = airborne();
+        a.ab = Abilities::none();
+        a.pi.press_jump(a.delta.now);
+        a.handle_events(&mut Jumping);
+        assert_eq!(a.mv.velocity.y, -300.0);
+    }
+
+    #[test]
+    fn wall_jump_kicks_away_from_the_wall() {
+        let mut a = airborne();
+        a.bb.pushes_right_wall = true;
+        a.pi.right = true;
+        a.mv.velocity.y = -2000.0;
+        a.update(&mut Jumping);
+        assert_eq!(a.mv.velocity.y, a.profile.wall_slide_speed);
+
+        a.pi.press_jump(a.delta.now);
+        a.handle_events(&mut Jumping);
+        assert_eq!(a.mv.velocity, Vector2::new(-a.profile.wall_jump_speed, a.profile.jump_speed));
+        assert_eq!(a.bb.cannot_go_right_frames, a.profile.wall_jump_lock_frames);
+        assert_eq!(a.ab.air_jumps_left, 1);
+
+        let mut a = airborne();
+        a.ab.wall_jump = false;
+        a.bb.pushes_right_wall = true;
+        a.pi.right = true;
+        a.mv.velocity.y = -2000.0;
+        a.update(&mut Jumping);
+        assert!(a.mv.velocity.y < a.profile.wall_slide_speed);
+    }
+
+    #[test]
+    fn coyote_time_allows_a_late_jump() {
+        let mut a = airborne();
+        a.ab = Abilities::none();
+        a.bb.grounded_at = a.delta.now - a.tuning.coyote_time / 2.0;
+        a.pi.press_jump(a.delta.now);
+        a.handle_events(&mut Jumping);
+        assert_eq!(a.mv.velocity.y, a.profile.jump_speed);
+
+        let mut a = airborne();
+        a.ab = Abilities::none();
+        a.bb.grounded_at = a.delta.now - a.tuning.coyote_time * 2.0;
+        a.pi.press_jump(a.delta.now);
+        a.handle_events(&mut Jumping);
+        assert_eq!(a.mv.velocity.y, -300.0);
+    }
+
+    #[test]
+    fn buffered_jump_fires_on_landing() {
+        let land = |a: &mut Actor| {
+            a.delta.now += a.tuning.jump_buffer / 2.0;
+            a.bb.on_ground = true;
+            a.bb.grounded_at = a.delta.now;
+            match a.update(&mut Jumping) {
+                Trans::Pop => (),
+                _ => panic!("should have landed"),
+            }
+            a.mv.velocity.y = 0.0;
+            a.start(&mut Idle);
+            a.handle_events(&mut Idle)
+        };
+
+        let mut a = airborne();
+        a.ab = Abilities::none();
+        a.pi.press_jump(a.delta.now);
+        a.handle_events(&mut Jumping);
+        match land(&mut a) {
+            Trans::Push(_) => (),
+            _ => panic!("buffered jump didn't fire"),
+        }
+        assert_eq!(a.mv.velocity.y, a.profile.jump_speed);
+
+        let mut a = airborne();
+        a.ab = Abilities::none();
+        a.pi.press_jump(a.delta.now - a.tuning.jump_buffer);
+        a.handle_events(&mut Jumping);
+        match land(&mut a) {
+            Trans::None => (),
+            _ => panic!("stale press shouldn't jump"),
+        }
+    }
+
+    #[test]
+    fn releasing_jump_cuts_the_rise() {
+        let mut a = airborne();
+        a.pi.press_jump(a.delta.now);
+        a.handle_events(&mut Jumping);
+        a.update(&mut Jumping);
+        assert!(a.mv.velocity.y > a.tuning.release_speed);
+
+        a.pi.release_jump(a.delta.now);
+        a.update(&mut Jumping);
+        assert_eq!(a.mv.velocity.y, a.tuning.release_speed);
+    }
+
+    #[test]
+    fn only_jumps_get_cut_short() {
+        // thrown up in the air without ever pressing jump
+        let mut a = airborne();
+        a.mv.velocity.y = a.profile.jump_speed;
+        a.update(&mut Jumping);
+        assert!(a.mv.velocity.y > a.tuning.release_speed);
+
+        // a wall kick goes its full height even on a tap
+        let mut a = airborne();
+        a.bb.pushes_right_wall = true;
+        a.pi.press_jump(a.delta.now);
+        a.pi.release_jump(a.delta.now);
+        a.handle_events(&mut Jumping);
+        a.update(&mut Jumping);
+        assert!(a.mv.velocity.y > a.tuning.release_speed);
+    }
+}
@@ -1,4 +1,5 @@
 use components::*;
+use player::state::Dead;
 use resources::*;
 
 state_machine!(
@@ -9,27 +10,13 @@ state_machine!(
     _anim: &mut HasAnimationSequence,
     _rend: &mut Renderable,
     _dir: &Directional,
-    _pi: &PlayerInput,
-    _delta: &DeltaTime
+    _pi: &InputState,
+    _delta: &DeltaTime,
+    _ab: &mut Abilities,
+    _jt: &JumpTuning,
+    _mp: &MovementProfile
 );
 
-#[allow(dead_code)]
-struct Dead;
-impl State for Dead {
-    fn update(
-        &mut self,
-        _: &mut MovingObject,
-        _: &mut HasAABB,
-        _: &mut HasAnimationSequence,
-        _: &mut Renderable,
-        _: &Directional,
-        _: &PlayerInput,
-        _: &DeltaTime,
-    ) -> Trans {
-        Trans::Quit
-    }
-}
-
 #[allow(dead_code)]
 fn funky() {
     let machine = StateMachine::new(Dead);
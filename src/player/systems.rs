@@ -1,20 +1,35 @@
 use components::*;
-use player::consts as PC;
+use player::RESPAWN_TIME;
+use player::state::{Dead, Idle};
+use player::state_machine::StateMachine;
 use rayon::iter::ParallelIterator;
 use resources::*;
 use specs::*;
+use util::Vector2;
 
-pub struct PlayerDirectionSystem;
-impl<'a> System<'a> for PlayerDirectionSystem {
+/// Hands every `Controlled` entity the state of its own device.
+pub struct HumanInputSystem;
+impl<'a> System<'a> for HumanInputSystem {
     type SystemData = (
-        WriteStorage<'a, Directional>,
         ReadStorage<'a, Controlled>,
-        Fetch<'a, PlayerInput>,
+        WriteStorage<'a, InputState>,
+        Fetch<'a, InputDevices>,
     );
 
-    fn run(&mut self, (mut directional, controlled, input): Self::SystemData) {
-        (&mut directional, &controlled).par_join().for_each(
-            |(dir, _)| if input.left ^ input.right {
+    fn run(&mut self, (controlled, mut inputs, devices): Self::SystemData) {
+        (&controlled, &mut inputs).par_join().for_each(|(c, input)| {
+            *input = devices.get(c.device).cloned().unwrap_or_else(InputState::new);
+        });
+    }
+}
+
+pub struct PlayerDirectionSystem;
+impl<'a> System<'a> for PlayerDirectionSystem {
+    type SystemData = (WriteStorage<'a, Directional>, ReadStorage<'a, InputState>);
+
+    fn run(&mut self, (mut directional, inputs): Self::SystemData) {
+        (&mut directional, &inputs).par_join().for_each(
+            |(dir, input)| if input.left ^ input.right {
                 if input.left {
                     *dir = Directional::Left;
                 } else {
@@ -26,15 +41,17 @@ impl<'a> System<'a> for PlayerDirectionSystem {
 }
 
 type SMSD<'a> = (
-    ReadStorage<'a, Controlled>,
+    ReadStorage<'a, InputState>,
     WriteStorage<'a, PlayerStateMachine>,
     WriteStorage<'a, MovingObject>,
     WriteStorage<'a, HasAABB>,
     WriteStorage<'a, HasAnimationSequence>,
     WriteStorage<'a, Renderable>,
     WriteStorage<'a, Directional>,
-    Fetch<'a, PlayerInput>,
+    WriteStorage<'a, Abilities>,
+    ReadStorage<'a, MovementProfile>,
     Fetch<'a, DeltaTime>,
+    Fetch<'a, JumpTuning>,
 );
 
 
@@ -43,19 +60,35 @@ impl<'a> System<'a> for PlayerUpdateSystem {
     type SystemData = SMSD<'a>;
 
     fn run(&mut self, data: Self::SystemData) {
-        let (controlled, mut sm, mut mv, mut bb, mut anim, mut rend, dir, input, time) = data;
+        let (
+            input,
+            mut sm,
+            mut mv,
+            mut bb,
+            mut anim,
+            mut rend,
+            dir,
+            mut ab,
+            mp,
+            time,
+            tuning,
+        ) = data;
 
         (
-            &controlled,
+            &input,
             &mut sm,
             &mut mv,
             &mut bb,
             &mut anim,
             &mut rend,
             &dir,
+            &mut ab,
+            &mp,
         ).par_join()
-            .for_each(|(_, sm, mv, bb, anim, rend, dir)| {
-                sm.machine.update(mv, bb, anim, rend, dir, &*input, &*time);
+            .for_each(|(pi, sm, mv, bb, anim, rend, dir, ab, mp)| {
+                sm.machine.update(
+                    mv, bb, anim, rend, dir, pi, &*time, ab, &*tuning, mp,
+                );
             })
     }
 }
@@ -65,19 +98,35 @@ impl<'a> System<'a> for PlayerFixedUpdateSystem {
     type SystemData = SMSD<'a>;
 
     fn run(&mut self, data: Self::SystemData) {
-        let (controlled, mut sm, mut mv, mut bb, mut anim, mut rend, dir, input, time) = data;
+        let (
+            input,
+            mut sm,
+            mut mv,
+            mut bb,
+            mut anim,
+            mut rend,
+            dir,
+            mut ab,
+            mp,
+            time,
+            tuning,
+        ) = data;
 
         (
-            &controlled,
+            &input,
             &mut sm,
             &mut mv,
             &mut bb,
             &mut anim,
             &mut rend,
             &dir,
+            &mut ab,
+            &mp,
         ).par_join()
-            .for_each(|(_, sm, mv, bb, anim, rend, dir)| {
-                sm.machine.fixed_update(mv, bb, anim, rend, dir, &*input, &*time);
+            .for_each(|(pi, sm, mv, bb, anim, rend, dir, ab, mp)| {
+                sm.machine.fixed_update(
+                    mv, bb, anim, rend, dir, pi, &*time, ab, &*tuning, mp,
+                );
             })
     }
 }
@@ -87,19 +136,35 @@ impl<'a> System<'a> for PlayerHandleEventsSystem {
     type SystemData = SMSD<'a>;
 
     fn run(&mut self, data: Self::SystemData) {
-        let (controlled, mut sm, mut mv, mut bb, mut anim, mut rend, dir, input, time) = data;
+        let (
+            input,
+            mut sm,
+            mut mv,
+            mut bb,
+            mut anim,
+            mut rend,
+            dir,
+            mut ab,
+            mp,
+            time,
+            tuning,
+        ) = data;
 
         (
-            &controlled,
+            &input,
             &mut sm,
             &mut mv,
             &mut bb,
             &mut anim,
             &mut rend,
             &dir,
+            &mut ab,
+            &mp,
         ).par_join()
-            .for_each(|(_, sm, mv, bb, anim, rend, dir)| {
-                sm.machine.handle_events(mv, bb, anim, rend, dir, &*input, &*time);
+            .for_each(|(pi, sm, mv, bb, anim, rend, dir, ab, mp)| {
+                sm.machine.handle_events(
+                    mv, bb, anim, rend, dir, pi, &*time, ab, &*tuning, mp,
+                );
             })
     }
 }
@@ -109,30 +174,34 @@ impl<'a> System<'a> for StartPSMSystem {
     type SystemData = (
         Entities<'a>,
         WriteStorage<'a, StartPSM>,
-        ReadStorage<'a, Controlled>,
+        ReadStorage<'a, InputState>,
         WriteStorage<'a, PlayerStateMachine>,
         WriteStorage<'a, MovingObject>,
         WriteStorage<'a, HasAABB>,
         WriteStorage<'a, HasAnimationSequence>,
         WriteStorage<'a, Renderable>,
         WriteStorage<'a, Directional>,
-        Fetch<'a, PlayerInput>,
+        WriteStorage<'a, Abilities>,
+        ReadStorage<'a, MovementProfile>,
         Fetch<'a, DeltaTime>,
+        Fetch<'a, JumpTuning>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
         let (
             e,
             mut start,
-            controlled,
+            input,
             mut sm,
             mut mv,
             mut bb,
             mut anim,
             mut rend,
             dir,
-            input,
+            mut ab,
+            mp,
             time,
+            tuning,
         ) = data;
 
         let mut rem = vec![];
@@ -140,16 +209,20 @@ impl<'a> System<'a> for StartPSMSystem {
         (
             &*e,
             &mut start,
-            &controlled,
+            &input,
             &mut sm,
             &mut mv,
             &mut bb,
             &mut anim,
             &mut rend,
             &dir,
+            &mut ab,
+            &mp,
         ).join()
-            .for_each(|(e, _, _, sm, mv, bb, anim, rend, dir)| {
-                sm.machine.start(mv, bb, anim, rend, dir, &*input, &*time);
+            .for_each(|(e, _, pi, sm, mv, bb, anim, rend, dir, ab, mp)| {
+                sm.machine.start(
+                    mv, bb, anim, rend, dir, pi, &*time, ab, &*tuning, mp,
+                );
                 rem.push(e);
             });
 
@@ -159,45 +232,217 @@ impl<'a> System<'a> for StartPSMSystem {
     }
 }
 
+/// Hazards are deadly, whoever touches one is killed and `DeathSystem` takes it from there.
+pub struct HazardSystem;
+impl<'a> System<'a> for HazardSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, HasAABB>,
+        WriteStorage<'a, Health>,
+        WriteStorage<'a, Killed>,
+    );
+
+    fn run(&mut self, (e, bb, mut health, mut killed): Self::SystemData) {
+        for (e, bb, health) in (&*e, &bb, &mut health).join() {
+            if bb.touches_hazard && !health.is_dead() {
+                health.kill();
+                killed.insert(e, Killed);
+            }
+        }
+    }
+}
+
+/// Kill zones work like hazards, whoever falls into one is killed.
+pub struct KillZoneSystem;
+impl<'a> System<'a> for KillZoneSystem {
+    type SystemData = (
+        WriteStorage<'a, Health>,
+        WriteStorage<'a, Killed>,
+        Fetch<'a, TriggerEvents>,
+    );
+
+    fn run(&mut self, (mut health, mut killed, events): Self::SystemData) {
+        for (_, e) in events.entered(TriggerKind::Kill) {
+            if let Some(health) = health.get_mut(e) {
+                if !health.is_dead() {
+                    health.kill();
+                    killed.insert(e, Killed);
+                }
+            }
+        }
+    }
+}
+
+/// Moves the level spawn to the bottom of the last checkpoint a player went through.
+pub struct CheckpointSystem;
+impl<'a> System<'a> for CheckpointSystem {
+    type SystemData = (
+        ReadStorage<'a, Controlled>,
+        ReadStorage<'a, Trigger>,
+        Fetch<'a, TriggerEvents>,
+        FetchMut<'a, LevelTerrain>,
+    );
+
+    fn run(&mut self, (controlled, triggers, events, mut level): Self::SystemData) {
+        for (t, e) in events.entered(TriggerKind::Checkpoint) {
+            if let (Some(_), Some(t)) = (controlled.get(e), triggers.get(t)) {
+                let v = t.volume;
+                level.spawn = Vector2::new(v.x + v.w / 2.0, v.y);
+            }
+        }
+    }
+}
+
+/// Swaps the state machine of whoever just got killed for one sitting in `Dead`.
+/// Players get a `Respawn` countdown, enemies stay down.
+pub struct DeathSystem;
+impl<'a> System<'a> for DeathSystem {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Controlled>,
+        WriteStorage<'a, Killed>,
+        WriteStorage<'a, PlayerStateMachine>,
+        WriteStorage<'a, StartPSM>,
+        WriteStorage<'a, Respawn>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (e, controlled, mut killed, mut sm, mut start, mut respawn) = data;
+        let mut rem = vec![];
+
+        for (e, _, sm) in (&*e, &killed, &mut sm).join() {
+            sm.machine = StateMachine::new(Dead);
+            start.insert(e, StartPSM);
+            if controlled.get(e).is_some() {
+                respawn.insert(e, Respawn { left: RESPAWN_TIME });
+            }
+            rem.push(e);
+        }
+
+        for e in rem.iter() {
+            killed.remove(e.clone());
+        }
+    }
+}
+
+/// Brings players whose countdown ran out back at the level spawn, with full health
+/// and a fresh body and state machine.
+pub struct RespawnSystem;
+impl<'a> System<'a> for RespawnSystem {
+    type SystemData = (
+        Entities<'a>,
+        WriteStorage<'a, Respawn>,
+        WriteStorage<'a, Health>,
+        WriteStorage<'a, MovingObject>,
+        WriteStorage<'a, HasAABB>,
+        WriteStorage<'a, PlayerStateMachine>,
+        WriteStorage<'a, StartPSM>,
+        Fetch<'a, LevelTerrain>,
+        Fetch<'a, DeltaTime>,
+    );
+
+    fn run(&mut self, data: Self::SystemData) {
+        let (e, mut respawn, mut health, mut mv, mut bb, mut sm, mut start, level, time) = data;
+        let mut rem = vec![];
+
+        for (e, r, health, mv, bb, sm) in
+            (&*e, &mut respawn, &mut health, &mut mv, &mut bb, &mut sm).join()
+        {
+            r.left -= time.delta;
+            if r.left > 0.0 {
+                continue;
+            }
+            *health = Health::new(health.max);
+            *mv = MovingObject::new(level.spawn);
+            *bb = HasAABB::new(bb.aabb.clone());
+            sm.machine = StateMachine::new(Idle);
+            start.insert(e, StartPSM);
+            rem.push(e);
+        }
+
+        for e in rem.iter() {
+            respawn.remove(e.clone());
+        }
+    }
+}
+
 pub struct ResetInputSystem;
 impl<'a> System<'a> for ResetInputSystem {
-    type SystemData = FetchMut<'a, PlayerInput>;
+    type SystemData = (WriteStorage<'a, InputState>, FetchMut<'a, InputDevices>);
 
-    fn run(&mut self, mut input: Self::SystemData) {
-        input.reset_actions();
+    fn run(&mut self, (mut inputs, mut devices): Self::SystemData) {
+        (&mut inputs).par_join().for_each(|input| input.reset_actions());
+        devices.reset_actions();
     }
 }
 
 pub struct PlayerAux;
 impl PlayerAux {
-    pub fn movement(mv: &mut MovingObject, bb: &HasAABB, direction: &Directional) {
+    pub fn movement(
+        mv: &mut MovingObject,
+        bb: &HasAABB,
+        direction: &Directional,
+        pi: &InputState,
+        mp: &MovementProfile,
+    ) {
+        let speed = pi.walk_scale * if bb.in_water {
+            mp.walk_speed * mp.water_speed_scale
+        } else {
+            mp.walk_speed
+        };
+
         match *direction {
             Directional::Left => if bb.pushes_left_wall {
                 PlayerAux::stop(mv);
             } else {
-                mv.accel.x = -PC::WALK_ACCEL;
-                mv.velocity.x = (-PC::WALK_SPEED / 2.0).min(mv.velocity.x).max(-PC::WALK_SPEED);
+                mv.accel.x = -mp.walk_accel;
+                mv.velocity.x = (-speed / 2.0).min(mv.velocity.x).max(-speed);
             },
             Directional::Right => if bb.pushes_right_wall {
                 PlayerAux::stop(mv);
             } else {
-                mv.accel.x = PC::WALK_ACCEL;
-                mv.velocity.x = (PC::WALK_SPEED / 2.0).max(mv.velocity.x).min(PC::WALK_SPEED);
+                mv.accel.x = mp.walk_accel;
+                mv.velocity.x = (speed / 2.0).max(mv.velocity.x).min(speed);
             },
         }
     }
 
+    /// Whether a jump press is recent enough to act on and hasn't been used up yet.
+    pub fn wants_jump(bb: &HasAABB, pi: &InputState, time: &DeltaTime, jt: &JumpTuning) -> bool {
+        match pi.jump_pressed_within(time.now, jt.jump_buffer) {
+            Some(pressed_at) => bb.jumped_at.map_or(true, |j| pressed_at > j),
+            None => false,
+        }
+    }
+
+    pub fn use_jump(bb: &mut HasAABB, time: &DeltaTime) {
+        bb.jumped_at = Some(time.now);
+        bb.input_jump = true;
+    }
+
+    /// Just walked off a ledge without jumping, a ground jump still counts.
+    pub fn in_coyote_time(
+        mv: &MovingObject,
+        bb: &HasAABB,
+        time: &DeltaTime,
+        jt: &JumpTuning,
+    ) -> bool {
+        let jumped_since = bb.jumped_at.map_or(false, |j| j >= bb.grounded_at);
+        !jumped_since && !bb.at_ceiling && mv.velocity.y <= 0.0 &&
+            time.now - bb.grounded_at <= jt.coyote_time
+    }
+
     pub fn stop(mv: &mut MovingObject) {
         mv.accel.x = 0.0;
         mv.velocity.x = 0.0;
     }
 
-    pub fn slow_down(mv: &mut MovingObject, fast: bool) {
+    pub fn slow_down(mv: &mut MovingObject, fast: bool, mp: &MovementProfile) {
         if fast {
-            if mv.velocity.x > PC::WALK_ACCEL / 2.0 {
-                mv.accel.x = -PC::WALK_ACCEL * 5.0;
-            } else if mv.velocity.x < -PC::WALK_ACCEL / 2.0 {
-                mv.accel.x = PC::WALK_ACCEL * 5.0;
+            if mv.velocity.x > mp.walk_accel / 2.0 {
+                mv.accel.x = -mp.walk_accel * 5.0;
+            } else if mv.velocity.x < -mp.walk_accel / 2.0 {
+                mv.accel.x = mp.walk_accel * 5.0;
             } else {
                 PlayerAux::stop(mv);
             }
@@ -206,3 +451,213 @@ impl PlayerAux {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use input::Action;
+    use level::{Terrain, TileType};
+    use physics::AABB;
+    use player::Player;
+    use player::animation_loader::{P_DEAD, P_IDLE};
+    use util::Vector2;
+
+    #[test]
+    fn each_player_gets_its_own_device() {
+        let mut world = World::new();
+        register_components(&mut world);
+
+        let mut devices = InputDevices::new();
+        devices.get_mut(InputDevice::Keyboard).left = true;
+        devices.get_mut(InputDevice::Controller(3)).right = true;
+        world.add_resource(devices);
+
+        let (keyboard, pad, unplugged) = {
+            let mut spawn = |device| {
+                world
+                    .create_entity()
+                    .with(Controlled { device })
+                    .with(InputState::new())
+                    .build()
+            };
+            (
+                spawn(InputDevice::Keyboard),
+                spawn(InputDevice::Controller(3)),
+                spawn(InputDevice::Controller(4)),
+            )
+        };
+
+        HumanInputSystem.run_now(&mut world.res);
+        let inputs = world.read::<InputState>();
+        let held = |e| {
+            let i = inputs.get(e).unwrap();
+            (i.left, i.right)
+        };
+        assert_eq!(held(keyboard), (true, false));
+        assert_eq!(held(pad), (false, true));
+        assert_eq!(held(unplugged), (false, false));
+    }
+
+    #[test]
+    fn a_half_pushed_stick_walks_at_half_speed() {
+        let bb = HasAABB::new(AABB::new_full(Vector2::new(64.0, 64.0), Vector2::new(1.0, 1.0)));
+        let mp = MovementProfile::default();
+        let mut pi = InputState::new();
+
+        let mut full = MovingObject::new(Vector2::new(0.0, 0.0));
+        PlayerAux::movement(&mut full, &bb, &Directional::Right, &pi, &mp);
+
+        pi.set_axis(Action::Right, 0.5, 0.0);
+        let mut half = MovingObject::new(Vector2::new(0.0, 0.0));
+        PlayerAux::movement(&mut half, &bb, &Directional::Right, &pi, &mp);
+
+        assert!(full.velocity.x > 0.0);
+        assert_eq!(half.velocity.x, full.velocity.x / 2.0);
+        assert!(pi.right);
+    }
+
+    // a closed room with a floor at y = 192
+    fn room() -> World {
+        let mut world = World::new();
+        register_components(&mut world);
+
+        let mut rows = vec![vec![TileType::Empty; 8]; 8];
+        for x in 0..8 {
+            rows[0][x] = TileType::Block;
+        }
+        let terrain = Terrain {
+            terrain: rows,
+            position: Vector2::new(0.0, 128.0),
+            width: 8,
+            height: 8,
+            tile_size: 128.0,
+        };
+        world.add_resource(LevelTerrain {
+            terrain,
+            spawn: Vector2::new(512.0, 400.0),
+        });
+        world.add_resource(DeltaTime::new());
+        world.add_resource(JumpTuning::new());
+        world.add_resource(InputDevices::new());
+        world
+    }
+
+    fn actor(world: &mut World, at: Vector2) -> Entity {
+        Player::actor(world, at, 0.4, 0, MovementProfile::default())
+            .with(Abilities::default())
+            .build()
+    }
+
+    fn jump_height(fps: f64) -> f64 {
+        let mut world = room();
+        let player = actor(&mut world, Vector2::new(512.0, 400.0));
+
+        let mut sim = DispatcherBuilder::new()
+            .add(StartPSMSystem, "start", &[])
+            .add(PlayerDirectionSystem, "direct", &[])
+            .add(PlayerHandleEventsSystem, "events", &["start", "direct"])
+            .add(AABBMovingSystem, "aabb", &["events"])
+            .add(PlayerUpdateSystem, "update", &["aabb"])
+            .add(ResetInputSystem, "reset", &["update"])
+            .build();
+
+        let mut clock = FixedStep::new();
+        let mut top = ::std::f64::MIN;
+        for _ in 0..(fps * 2.0) as usize {
+            clock.add(1.0 / fps);
+            while let Some(fixed) = clock.next() {
+                // a second to land, then jump and keep holding it
+                if clock.steps == 60 {
+                    let now = world.read_resource::<DeltaTime>().now;
+                    world.write::<InputState>().get_mut(player).unwrap().press_jump(now);
+                }
+                world.write_resource::<DeltaTime>().advance(clock.step);
+                if fixed {
+                    PlayerFixedUpdateSystem.run_now(&mut world.res);
+                }
+                sim.dispatch(&mut world.res);
+                world.maintain();
+
+                let y = world.read::<MovingObject>().get(player).unwrap().position.y;
+                top = top.max(y);
+            }
+        }
+        top
+    }
+
+    #[test]
+    fn jump_height_does_not_depend_on_the_frame_rate() {
+        let at_60 = jump_height(60.0);
+        assert!(at_60 > 600.0);
+        assert_eq!(jump_height(30.0), at_60);
+        assert_eq!(jump_height(300.0), at_60);
+    }
+
+    fn animation(world: &World, e: Entity) -> &'static str {
+        match world.read::<Renderable>().get(e).unwrap().tpe {
+            RenderableType::Animation { id, .. } => id,
+            _ => "",
+        }
+    }
+
+    #[test]
+    fn dead_players_come_back_at_the_spawn() {
+        let mut world = room();
+        let player = actor(&mut world, Vector2::new(200.0, 300.0));
+        let enemy = actor(&mut world, Vector2::new(800.0, 300.0));
+        world.write::<Controlled>().insert(player, Controlled { device: InputDevice::Keyboard });
+        for &e in [player, enemy].iter() {
+            let mut health = Health::new(100.0);
+            health.current = 0.0;
+            world.write::<Health>().insert(e, health);
+            world.write::<Killed>().insert(e, Killed);
+        }
+
+        DeathSystem.run_now(&mut world.res);
+        StartPSMSystem.run_now(&mut world.res);
+        assert!(world.read::<Killed>().get(player).is_none());
+        assert_eq!(animation(&world, player), P_DEAD);
+        assert_eq!(animation(&world, enemy), P_DEAD);
+        assert!(world.read::<Respawn>().get(enemy).is_none());
+
+        world.write_resource::<DeltaTime>().advance(RESPAWN_TIME / 2.0);
+        RespawnSystem.run_now(&mut world.res);
+        assert!(world.read::<Health>().get(player).unwrap().is_dead());
+
+        world.write_resource::<DeltaTime>().advance(RESPAWN_TIME / 2.0);
+        RespawnSystem.run_now(&mut world.res);
+        StartPSMSystem.run_now(&mut world.res);
+        assert!(world.read::<Respawn>().get(player).is_none());
+        assert!(!world.read::<Health>().get(player).unwrap().is_dead());
+        assert_eq!(animation(&world, player), P_IDLE);
+        assert_eq!(
+            world.read::<MovingObject>().get(player).unwrap().position,
+            Vector2::new(512.0, 400.0)
+        );
+        assert!(world.read::<Health>().get(enemy).unwrap().is_dead());
+    }
+
+    #[test]
+    fn hazards_kill_whatever_has_health() {
+        let mut world = room();
+        let player = actor(&mut world, Vector2::new(200.0, 300.0));
+        world.write::<Health>().insert(player, Health::new(100.0));
+        let rock = world
+            .create_entity()
+            .with(MovingObject::new(Vector2::new(600.0, 300.0)))
+            .with(HasAABB::new(AABB::new_full(Vector2::new(64.0, 64.0), Vector2::new(1.0, 1.0))))
+            .build();
+        for &e in [player, rock].iter() {
+            world.write::<HasAABB>().get_mut(e).unwrap().touches_hazard = true;
+        }
+
+        HazardSystem.run_now(&mut world.res);
+        assert!(world.read::<Health>().get(player).unwrap().is_dead());
+        assert!(world.read::<Killed>().get(player).is_some());
+        assert!(world.read::<Killed>().get(rock).is_none());
+        assert_eq!(
+            world.read::<MovingObject>().get(rock).unwrap().position,
+            Vector2::new(600.0, 300.0)
+        );
+    }
+}
@@ -21,6 +21,7 @@ use ggez::GameResult;
 use ggez::graphics;
 use ggez::graphics::DrawParam;
 use na;
+use physics::Volume;
 use util::Vector2;
 
 // Hmm.  Could, instead, use a 2d transformation
@@ -50,6 +51,21 @@ impl Camera {
         self.view_center = to;
     }
 
+    /// Moves the view the least it takes to show nothing outside `area`, or centers
+    /// it along an axis where the area is smaller than the view.
+    pub fn keep_inside(&mut self, area: &Volume) {
+        let half = self.view_size / 2.0;
+        let clamp = |center: f64, from: f64, size: f64, half: f64| if size <= half * 2.0 {
+            from + size / 2.0
+        } else {
+            center.max(from + half).min(from + size - half)
+        };
+        self.view_center = Vector2::new(
+            clamp(self.view_center.x, area.x, area.w, half.x),
+            clamp(self.view_center.y, area.y, area.h, half.y),
+        );
+    }
+
     pub fn draw_scale(&self) -> graphics::Point2 {
         graphics::Point2::new(
             (self.screen_size.x / self.view_size.x) as f32,
@@ -196,4 +212,19 @@ mod tests {
             assert_eq!(p2_world, p2);
         }
     }
+
+    #[test]
+    fn stays_inside_the_area() {
+        let mut c = Camera::new(640, 480, 40.0, 30.0);
+        let area = Volume::new(0.0, 0.0, 100.0, 20.0);
+
+        c.move_to(Vector2::new(5.0, 50.0));
+        c.keep_inside(&area);
+        // pushed off the left edge, too short to fit so centered vertically
+        assert_eq!(c.location(), Vector2::new(20.0, 10.0));
+
+        c.move_to(Vector2::new(50.0, 0.0));
+        c.keep_inside(&area);
+        assert_eq!(c.location(), Vector2::new(50.0, 10.0));
+    }
 }
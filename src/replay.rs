@@ -0,0 +1,145 @@
+use components::{InputDevice, InputState};
+use ggez::{GameError, GameResult};
+use serde_json;
+use std::fs::File;
+use std::path::Path;
+use util::Vector2;
+
+pub const REPLAY: &str = "replay.json";
+
+/// Things that happen between frames and change the world outside of input.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ReplayEvent {
+    Join(InputDevice),
+    SpawnEnemy(f64, f64),
+}
+
+impl ReplayEvent {
+    pub fn spawn_enemy(at: Vector2) -> ReplayEvent {
+        ReplayEvent::SpawnEnemy(at.x, at.y)
+    }
+}
+
+/// One simulation step.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    pub delta: f64,
+    /// Whether the fixed update systems ran this step.
+    pub fixed: bool,
+    /// What every device held going into the step.
+    pub devices: Vec<(InputDevice, InputState)>,
+    #[serde(default)]
+    pub events: Vec<ReplayEvent>,
+}
+
+/// A recorded session, everything needed to play it back exactly in the same build
+/// with the same level list.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Replay {
+    pub level: usize,
+    pub seed: u32,
+    /// Devices that had a player when the recording started.
+    pub devices: Vec<InputDevice>,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn new(level: usize, seed: u32, devices: Vec<InputDevice>) -> Replay {
+        Replay {
+            level,
+            seed,
+            devices,
+            frames: vec![],
+        }
+    }
+
+    pub fn load(path: &Path) -> GameResult<Replay> {
+        let file = File::open(path)?;
+        serde_json::from_reader(file).map_err(|e| {
+            GameError::ResourceLoadError(format!("{}: {}", path.display(), e))
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> GameResult<()> {
+        let file = File::create(path)?;
+        serde_json::to_writer(file, self).map_err(|e| {
+            GameError::UnknownError(format!("{}: {}", path.display(), e))
+        })
+    }
+}
+
+pub struct Playback {
+    pub replay: Replay,
+    next: usize,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Playback {
+        Playback { replay, next: 0 }
+    }
+
+    pub fn next_frame(&mut self) -> Option<ReplayFrame> {
+        let frame = self.replay.frames.get(self.next).cloned();
+        self.next += 1;
+        frame
+    }
+}
+
+pub enum Session {
+    Live,
+    Recording(Replay),
+    Playing(Playback),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use resources::{FixedStep, InputDevices};
+    use std::env;
+
+    #[test]
+    fn survives_a_round_trip_through_a_file() {
+        let mut devices = InputDevices::new();
+        devices.get_mut(InputDevice::Keyboard).press_jump(0.5);
+        devices.get_mut(InputDevice::Controller(2)).walk_scale = 0.25;
+
+        let mut clock = FixedStep::new();
+        let step = clock.step;
+        clock.add(step * 3.5);
+
+        let mut replay = Replay::new(1, 42, vec![InputDevice::Keyboard]);
+        for i in 0..3 {
+            replay.frames.push(ReplayFrame {
+                delta: step,
+                fixed: clock.next().unwrap(),
+                devices: devices.snapshot(),
+                events: if i == 1 {
+                    vec![ReplayEvent::spawn_enemy(Vector2::new(10.0, 20.0))]
+                } else {
+                    vec![]
+                },
+            });
+        }
+
+        let path = env::temp_dir().join("replay-test.json");
+        replay.save(&path).unwrap();
+        let mut playback = Playback::new(Replay::load(&path).unwrap());
+        assert_eq!((playback.replay.level, playback.replay.seed), (1, 42));
+
+        let fixed = (0..3)
+            .map(|_| playback.next_frame().unwrap())
+            .map(|f| {
+                let mut restored = InputDevices::new();
+                restored.restore(&f.devices);
+                let kb = restored.get(InputDevice::Keyboard).unwrap();
+                assert_eq!(kb.jump_pressed_at, Some(0.5));
+                let pad = restored.get(InputDevice::Controller(2)).unwrap();
+                assert_eq!(pad.walk_scale, 0.25);
+                assert_eq!(f.delta, step);
+                f.fixed
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(fixed, vec![true, false, true]);
+        assert!(playback.next_frame().is_none());
+    }
+}
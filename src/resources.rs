@@ -1,42 +1,284 @@
+use components::{InputDevice, InputState};
 use level::*;
+use physics::Volume;
+use physics::components::TriggerKind;
+use rand::{SeedableRng, XorShiftRng};
+use specs::Entity;
+use std::collections::HashMap;
+use util::Vector2;
 
 #[derive(Clone)]
 pub struct DeltaTime {
     pub delta: f64,
+    /// Seconds of game time since start, the clock input timestamps use.
+    pub now: f64,
 }
 
-pub struct LevelTerrain {
-    pub terrain: Terrain,
+impl DeltaTime {
+    pub fn new() -> DeltaTime {
+        DeltaTime {
+            delta: 0.0,
+            now: 0.0,
+        }
+    }
+
+    pub fn advance(&mut self, delta: f64) {
+        self.delta = delta;
+        self.now += delta;
+    }
+}
+
+/// Splits frame time into equal simulation steps, so the simulation doesn't depend on
+/// the frame rate. Whatever is left over tells rendering how far it is into the next step.
+pub struct FixedStep {
+    pub step: f64,
+    /// Fixed updates, like animation frames, run on every n-th step.
+    pub fixed_update_every: u64,
+    /// Longest frame that still gets simulated in full, anything longer slows the game down
+    /// instead of piling up steps.
+    pub max_frame: f64,
+    pub steps: u64,
+    accumulator: f64,
+}
+
+impl FixedStep {
+    pub fn new() -> FixedStep {
+        FixedStep {
+            step: 1.0 / 60.0,
+            fixed_update_every: 2,
+            max_frame: 0.25,
+            steps: 0,
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn add(&mut self, frame: f64) {
+        self.accumulator += frame.min(self.max_frame);
+    }
+
+    /// Takes a step if enough time has built up, with whether it's also a fixed update.
+    pub fn next(&mut self) -> Option<bool> {
+        if self.accumulator < self.step {
+            return None;
+        }
+        self.accumulator -= self.step;
+        let fixed = self.steps % self.fixed_update_every == 0;
+        self.steps += 1;
+        Some(fixed)
+    }
+
+    /// How far between the last two steps rendering is, from 0.0 to 1.0.
+    pub fn alpha(&self) -> f64 {
+        self.accumulator / self.step
+    }
 }
 
+/// How forgiving jumping is, all times are in seconds.
 #[derive(Clone)]
-pub struct PlayerInput {
-    pub up: bool,
-    pub down: bool,
-    pub left: bool,
-    pub right: bool,
-    pub slide: bool,
-    pub jump: bool,
-    pub attack: bool,
-}
-
-impl PlayerInput {
-    pub fn new() -> PlayerInput {
-        PlayerInput {
-            up: false,
-            down: false,
-            left: false,
-            right: false,
-            slide: false,
-            jump: false,
-            attack: false,
+pub struct JumpTuning {
+    /// How long after walking off a ledge a jump still counts.
+    pub coyote_time: f64,
+    /// How long before landing a jump press is remembered.
+    pub jump_buffer: f64,
+    /// Upward speed a jump is cut down to when the button is released early.
+    pub release_speed: f64,
+}
+
+impl JumpTuning {
+    pub fn new() -> JumpTuning {
+        JumpTuning {
+            coyote_time: 0.1,
+            jump_buffer: 0.12,
+            release_speed: 600.0,
         }
     }
+}
+
+pub struct LevelTerrain {
+    pub terrain: Terrain,
+    pub spawn: Vector2,
+}
+
+/// What every input device currently has pressed, entities pick theirs up through
+/// `Controlled`.
+pub struct InputDevices {
+    states: HashMap<InputDevice, InputState>,
+}
+
+impl InputDevices {
+    pub fn new() -> InputDevices {
+        InputDevices { states: HashMap::new() }
+    }
+
+    pub fn get(&self, device: InputDevice) -> Option<&InputState> {
+        self.states.get(&device)
+    }
+
+    pub fn get_mut(&mut self, device: InputDevice) -> &mut InputState {
+        self.states.entry(device).or_insert_with(InputState::new)
+    }
+
+    pub fn snapshot(&self) -> Vec<(InputDevice, InputState)> {
+        self.states.iter().map(|(&d, input)| (d, input.clone())).collect()
+    }
+
+    /// Replaces every device's state with the snapshot, devices missing from it are let go.
+    pub fn restore(&mut self, snapshot: &[(InputDevice, InputState)]) {
+        self.states = snapshot.iter().cloned().collect();
+    }
 
     pub fn reset_actions(&mut self) {
-        self.attack = false;
-        self.slide = false;
-        self.jump = false;
+        for input in self.states.values_mut() {
+            input.reset_actions();
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Response {
+    Ignore,
+    /// Shows up in `CollisionEvents`, but the bodies pass through each other.
+    Report,
+    /// Pushed apart and reported.
+    Resolve,
+}
+
+/// What happens when bodies on two layers touch. Gameplay code sets the layers up,
+/// anything not set is ignored.
+pub struct CollisionLayers {
+    responses: Vec<Vec<Response>>,
+}
+
+impl CollisionLayers {
+    pub fn new(layers: usize) -> CollisionLayers {
+        CollisionLayers { responses: vec![vec![Response::Ignore; layers]; layers] }
+    }
+
+    /// Adds a layer that ignores everything for now, returns its index.
+    pub fn add_layer(&mut self) -> usize {
+        for row in self.responses.iter_mut() {
+            row.push(Response::Ignore);
+        }
+        let layers = self.responses.len() + 1;
+        self.responses.push(vec![Response::Ignore; layers]);
+        layers - 1
+    }
+
+    pub fn set(&mut self, a: usize, b: usize, response: Response) {
+        self.responses[a][b] = response;
+        self.responses[b][a] = response;
+    }
+
+    pub fn response(&self, a: usize, b: usize) -> Response {
+        self.responses
+            .get(a)
+            .and_then(|row| row.get(b))
+            .cloned()
+            .unwrap_or(Response::Ignore)
+    }
+}
+
+/// Two bodies touching, found by `CollisionSystem` during the last step.
+#[derive(Debug, Clone, Copy)]
+pub struct Collision {
+    pub a: Entity,
+    pub b: Entity,
+    /// Which way `a` was pushed out of `b`, the other way for `b`.
+    pub normal: Vector2,
+    /// How far they overlapped, zero when `a` is only resting on `b`.
+    pub depth: f64,
+}
+
+pub struct CollisionEvents {
+    pub events: Vec<Collision>,
+}
+
+impl CollisionEvents {
+    pub fn new() -> CollisionEvents {
+        CollisionEvents { events: vec![] }
+    }
+
+    /// Contacts the entity took part in, as `a` or `b`.
+    pub fn involving(&self, e: Entity) -> Vec<Collision> {
+        self.events
+            .iter()
+            .filter(|c| c.a == e || c.b == e)
+            .cloned()
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overlap {
+    Enter,
+    Stay,
+    Exit,
+}
+
+/// A body going into, staying in or leaving a `Trigger`.
+#[derive(Debug, Clone, Copy)]
+pub struct TriggerEvent {
+    pub trigger: Entity,
+    pub kind: TriggerKind,
+    pub other: Entity,
+    pub overlap: Overlap,
+}
+
+/// Filled by `TriggerSystem` every step, any system after it can read them.
+pub struct TriggerEvents {
+    pub events: Vec<TriggerEvent>,
+}
+
+impl TriggerEvents {
+    pub fn new() -> TriggerEvents {
+        TriggerEvents { events: vec![] }
+    }
+
+    /// Bodies that went into a trigger of the kind this step, with the trigger.
+    pub fn entered(&self, kind: TriggerKind) -> Vec<(Entity, Entity)> {
+        self.events
+            .iter()
+            .filter(|t| t.kind == kind && t.overlap == Overlap::Enter)
+            .map(|t| (t.trigger, t.other))
+            .collect()
+    }
+}
+
+/// Camera trigger the camera's target is in, the view stays inside its volume.
+pub struct CameraZone(pub Option<(Entity, Volume)>);
+
+/// Seed for everything random in the simulation, so replays come out the same.
+pub struct RandomSeed(pub u32);
+
+impl RandomSeed {
+    /// A generator depending only on the seed, an entity and the game time, so systems
+    /// running in parallel give the same numbers whatever order they run in.
+    pub fn rng(&self, id: u32, now: f64) -> XorShiftRng {
+        let t = now.to_bits();
+        XorShiftRng::from_seed([self.0, id, t as u32, (t >> 32) as u32 | 1])
+    }
+}
+
+/// Attack hitboxes of every animation frame, keyed like the animations in `AssetStorage`.
+pub struct AttackHitboxes {
+    frames: HashMap<&'static str, Vec<Vec<Volume>>>,
+}
+
+impl AttackHitboxes {
+    pub fn new() -> AttackHitboxes {
+        AttackHitboxes { frames: HashMap::new() }
+    }
+
+    pub fn insert(&mut self, id: &'static str, frames: Vec<Vec<Volume>>) {
+        self.frames.insert(id, frames);
+    }
+
+    pub fn get(&self, id: &str, frame: usize) -> &[Volume] {
+        self.frames
+            .get(id)
+            .and_then(|frames| frames.get(frame))
+            .map(|hitboxes| &hitboxes[..])
+            .unwrap_or(&[])
     }
 }
 
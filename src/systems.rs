@@ -4,10 +4,13 @@ use components::*;
 use ggez::Context;
 use ggez::graphics::*;
 use rayon::iter::ParallelIterator;
+use resources::{CameraZone, Overlap, TriggerEvents};
 use specs::*;
 use std::collections::BTreeMap;
 use util::Vector2;
 
+pub use ai::systems::*;
+pub use combat::systems::*;
 pub use physics::systems::*;
 pub use player::systems::*;
 
@@ -127,14 +130,48 @@ impl<'a> System<'a> for CameraSnapSystem {
         FetchMut<'a, Camera>,
         ReadStorage<'a, Position>,
         ReadStorage<'a, SnapCamera>,
+        Fetch<'a, CameraZone>,
     );
 
     fn run(&mut self, data: Self::SystemData) {
-        let (mut camera, position, snap) = data;
+        let (mut camera, position, snap, zone) = data;
 
         for (p, _) in (&position, &snap).join() {
             camera.move_to(Vector2::new(p.x as f64, p.y as f64));
         }
+        if let Some((_, area)) = zone.0 {
+            camera.keep_inside(&area);
+        }
+    }
+}
+
+/// Follows the camera's target in and out of camera triggers.
+pub struct CameraZoneSystem;
+impl<'a> System<'a> for CameraZoneSystem {
+    type SystemData = (
+        ReadStorage<'a, SnapCamera>,
+        ReadStorage<'a, Trigger>,
+        Fetch<'a, TriggerEvents>,
+        FetchMut<'a, CameraZone>,
+    );
+
+    fn run(&mut self, (snap, triggers, events, mut zone): Self::SystemData) {
+        for t in events.events.iter() {
+            if t.kind != TriggerKind::Camera || snap.get(t.other).is_none() {
+                continue;
+            }
+            match t.overlap {
+                Overlap::Enter => {
+                    if let Some(trigger) = triggers.get(t.trigger) {
+                        zone.0 = Some((t.trigger, trigger.volume));
+                    }
+                }
+                Overlap::Exit => if zone.0.map_or(false, |(e, _)| e == t.trigger) {
+                    zone.0 = None;
+                },
+                Overlap::Stay => (),
+            }
+        }
     }
 }
 